@@ -0,0 +1,702 @@
+/* The consistency checker of filesystem, which is used by fsck on host.
+ * The image is checked by the following passes:
+ * 1. Scan every inode for invalid type or size, and collect the blocks
+ *    it refers to. A block can only be owned by one inode.
+ * 2. Compare the block bitmap with the blocks in use.
+ * 3. Walk through the directory tree from root to verify each entry,
+ *    and count the links to each inode.
+ * 4. Compare the link count of each inode with the one counted. The
+ *    inode which can't be reached from root is an orphan.
+ * The bitmap is fixed before walking the directories, so the blocks
+ * allocated to fix a directory won't conflict with any inode. */
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::device::BlockDevice;
+use crate::filesystem::FileSystem;
+use crate::inode::{indirect_link, set_indirect_link};
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Problem {
+    // The root inode is not a directory, so the tree can't be checked
+    BadRoot,
+    // The inode has an unknown type
+    BadType {
+        inum: u32,
+        typ: u16,
+    },
+    // The size of inode is too large, or not aligned for directory
+    BadSize {
+        inum: u32,
+        size: u32,
+    },
+    // The inode refers to a block out of the data region
+    BadBlock {
+        inum: u32,
+        block_no: u32,
+    },
+    // The block is already owned by another inode
+    DupBlock {
+        inum: u32,
+        block_no: u32,
+        owner: u32,
+    },
+    // The block is in use but marked as free
+    BlockUnmarked {
+        block_no: u32,
+    },
+    // The block is not in use but marked as allocated
+    BlockLeaked {
+        block_no: u32,
+    },
+    // The entry refers to a free or invalid inode, or has no name
+    BadDirent {
+        dir: u32,
+        off: usize,
+        inum: u32,
+    },
+    // The entry is corrupted, so the rest of the block is dropped
+    BadRecord {
+        dir: u32,
+        off: usize,
+    },
+    // The "." entry is missing or doesn't refer to the directory itself
+    BadDot {
+        dir: u32,
+    },
+    // The ".." entry is missing or doesn't refer to the parent
+    BadDotDot {
+        dir: u32,
+        parent: u32,
+    },
+    // The inode is allocated but not linked to any directory
+    Orphan {
+        inum: u32,
+    },
+    // The link count of inode mismatches the number of entries to it
+    LinkCount {
+        inum: u32,
+        nlink: u16,
+        refs: u32,
+    },
+}
+
+impl Problem {
+    // Whether the problem can be fixed by Checker
+    pub fn repairable(&self) -> bool {
+        *self != Problem::BadRoot
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::BadRoot => write!(f, "root inode {} is not a directory", ROOTINO),
+            Problem::BadType { inum, typ } => write!(f, "inode {} has unknown type {}", inum, typ),
+            Problem::BadSize { inum, size } => {
+                write!(f, "inode {} has invalid size {}", inum, size)
+            }
+            Problem::BadBlock { inum, block_no } => {
+                write!(f, "inode {} refers to invalid block {}", inum, block_no)
+            }
+            Problem::DupBlock {
+                inum,
+                block_no,
+                owner,
+            } => write!(
+                f,
+                "inode {} refers to block {} which is owned by inode {}",
+                inum, block_no, owner
+            ),
+            Problem::BlockUnmarked { block_no } => {
+                write!(f, "block {} is in use but marked free", block_no)
+            }
+            Problem::BlockLeaked { block_no } => {
+                write!(f, "block {} is marked in use but unreferenced", block_no)
+            }
+            Problem::BadDirent { dir, off, inum } => write!(
+                f,
+                "directory {} has invalid entry at offset {} to inode {}",
+                dir, off, inum
+            ),
+            Problem::BadRecord { dir, off } => {
+                write!(f, "directory {} has corrupted entry at offset {}", dir, off)
+            }
+            Problem::BadDot { dir } => write!(f, "directory {} has bad '.' entry", dir),
+            Problem::BadDotDot { dir, parent } => write!(
+                f,
+                "directory {} has bad '..' entry, expected inode {}",
+                dir, parent
+            ),
+            Problem::Orphan { inum } => write!(f, "inode {} is not linked anywhere", inum),
+            Problem::LinkCount { inum, nlink, refs } => write!(
+                f,
+                "inode {} has link count {}, but {} links are found",
+                inum, nlink, refs
+            ),
+        }
+    }
+}
+
+struct Checker<'a, D: BlockDevice> {
+    fs: &'a FileSystem<D>,
+    repair: bool,
+    problems: Vec<Problem>,
+    // The type of each inode, 0 if it is free
+    types: Vec<u16>,
+    // The inode which owns each block, 0 if there's none
+    owner: Vec<u32>,
+    // The number of directory entries to each inode
+    refs: Vec<u32>,
+}
+
+impl<'a, D: BlockDevice> Checker<'a, D> {
+    fn new(fs: &'a FileSystem<D>, repair: bool) -> Self {
+        let sb = fs.sb();
+        Checker {
+            fs,
+            repair,
+            problems: Vec::new(),
+            types: vec![0; sb.ninodes as usize + 1],
+            owner: vec![0; sb.fs_blksz as usize],
+            refs: vec![0; sb.ninodes as usize + 1],
+        }
+    }
+
+    // Take the block for the inode, return false if it is not allowed
+    fn claim(&mut self, inum: u32, block_no: u32) -> bool {
+        let sb = self.fs.sb();
+        if block_no < sb.nmeta() || block_no >= sb.fs_blksz {
+            self.problems.push(Problem::BadBlock { inum, block_no });
+            return false;
+        }
+
+        let owner = self.owner[block_no as usize];
+        if owner != 0 {
+            self.problems.push(Problem::DupBlock {
+                inum,
+                block_no,
+                owner,
+            });
+            return false;
+        }
+
+        self.owner[block_no as usize] = inum;
+        true
+    }
+
+    /* Claim all the blocks of inode. The invalid links are dropped if we
+     * are repairing, and return true if the inode is modified. */
+    fn check_blocks(&mut self, inum: u32, inode: &mut Inode) -> bool {
+        let mut dirty = false;
+
+        for bn in 0..NDIRECT {
+            let block_no = inode.directs[bn];
+            if block_no != 0 && !self.claim(inum, block_no) && self.repair {
+                inode.directs[bn] = 0;
+                dirty = true;
+            }
+        }
+
+        if inode.indirect == 0 {
+            return dirty;
+        }
+
+        if !self.claim(inum, inode.indirect) {
+            if self.repair {
+                inode.indirect = 0;
+                dirty = true;
+            }
+            return dirty;
+        }
+
+        let mut buf = vec![0; BLKSZ];
+        let mut buf_dirty = false;
+        self.fs.device().read_block(inode.indirect, &mut buf);
+        for n in 0..NINDIRECT {
+            let block_no = indirect_link(&buf, n);
+            if block_no != 0 && !self.claim(inum, block_no) && self.repair {
+                set_indirect_link(&mut buf, n, 0);
+                buf_dirty = true;
+            }
+        }
+        if buf_dirty {
+            self.fs.device().write_block(inode.indirect, &buf);
+        }
+
+        dirty
+    }
+
+    // Pass 1: check the inodes and collect the blocks in use
+    fn check_inodes(&mut self) {
+        for inum in 1..=self.fs.sb().ninodes {
+            let mut inode = self.fs.read_inode(inum);
+            if inode.is_free() {
+                continue;
+            }
+
+            let mut dirty = false;
+            let typ = inode.typ;
+            if !matches!(typ, T_DIR | T_FILE | T_DEVICE | T_SYMLINK) {
+                self.problems.push(Problem::BadType { inum, typ });
+                if self.repair {
+                    // The blocks are left for the bitmap pass to release
+                    inode.set_free();
+                    self.fs.write_inode(inum, &inode);
+                }
+                continue;
+            }
+
+            let max_size = if typ == T_SYMLINK {
+                SYMLINK_MAX as u32
+            } else {
+                (FILE_MAX_LINK * BLKSZ) as u32
+            };
+            let size = inode.size;
+            if size > max_size || (typ == T_DIR && size as usize % BLKSZ != 0) {
+                self.problems.push(Problem::BadSize { inum, size });
+                if self.repair {
+                    let size = size.min(max_size);
+                    inode.size = if typ == T_DIR {
+                        size - size % BLKSZ as u32
+                    } else {
+                        size
+                    };
+                    dirty = true;
+                }
+            }
+
+            dirty |= self.check_blocks(inum, &mut inode);
+            if dirty {
+                self.fs.write_inode(inum, &inode);
+            }
+
+            self.types[inum as usize] = typ;
+        }
+    }
+
+    // Pass 2: compare the bitmap with the blocks in use
+    fn check_bitmap(&mut self) {
+        let sb = *self.fs.sb();
+        let mut buf = vec![0; BLKSZ];
+
+        for block_no in 0..sb.fs_blksz {
+            let bit = block_no as usize % BIT_PER_BLK;
+            if bit == 0 {
+                self.fs
+                    .device()
+                    .read_block(block_bmap(&sb, block_no), &mut buf);
+            }
+
+            let marked = buf[bit / 8] & (1 << (bit % 8)) != 0;
+            let used = block_no < sb.nmeta() || self.owner[block_no as usize] != 0;
+            if used == marked {
+                continue;
+            }
+
+            if used {
+                self.problems.push(Problem::BlockUnmarked { block_no });
+            } else {
+                self.problems.push(Problem::BlockLeaked { block_no });
+            }
+            if self.repair {
+                self.fs.set_bitmap(block_no, used);
+            }
+        }
+    }
+
+    fn is_valid_inum(&self, inum: u32) -> bool {
+        inum != 0 && inum <= self.fs.sb().ninodes && self.types[inum as usize] != 0
+    }
+
+    // Change the inode number of the entry at the offset of directory
+    fn set_dirent(&self, dir: &mut Inode, off: usize, mut dirent: Dirent, inum: u32) {
+        dirent.inum = inum;
+        assert!(self.fs.write_dirent(dir, off, &dirent));
+    }
+
+    /* Drop the corrupted entries from the offset to the end of block, by
+     * extending the previous entry in the block, or an unused entry. */
+    fn drop_dirents(&self, dir: &mut Inode, off: usize, prev: Option<(usize, Dirent)>) {
+        let end = (off / BLKSZ + 1) * BLKSZ;
+        let (off, mut dirent) = match prev {
+            Some((prev_off, dirent)) if prev_off / BLKSZ == off / BLKSZ => (prev_off, dirent),
+            _ => (off, Dirent::new(0, "").unwrap()),
+        };
+        dirent.rec_len = (end - off) as u16;
+        assert!(self.fs.write_dirent(dir, off, &dirent));
+    }
+
+    /* Check the entries of the directory, count the links to the inodes
+     * and return the subdirectories in it. */
+    fn check_dir(&mut self, inum: u32, parent: u32) -> Vec<u32> {
+        let mut dir = self.fs.read_inode(inum);
+        let mut subdirs = Vec::new();
+        let mut dirty = false;
+        let mut has_dot = false;
+        let mut has_dotdot = false;
+
+        let mut off = 0;
+        let mut prev = None;
+        while off < dir.size as usize {
+            let dirent = match self.fs.read_dirent(&dir, off) {
+                Some(dirent) => dirent,
+                None => {
+                    // Skip to the next block, which may be still valid
+                    self.problems.push(Problem::BadRecord { dir: inum, off });
+                    if self.repair {
+                        self.drop_dirents(&mut dir, off, prev);
+                        dirty = true;
+                    }
+                    off = (off / BLKSZ + 1) * BLKSZ;
+                    prev = None;
+                    continue;
+                }
+            };
+            let entry_off = off;
+            off += dirent.rec_len as usize;
+            prev = Some((entry_off, dirent));
+
+            let target = dirent.inum;
+            if target == 0 {
+                continue;
+            }
+
+            match dirent.name() {
+                b"." => {
+                    has_dot = true;
+                    if target != inum {
+                        self.problems.push(Problem::BadDot { dir: inum });
+                        if self.repair {
+                            self.set_dirent(&mut dir, entry_off, dirent, inum);
+                            dirty = true;
+                        }
+                    }
+                    // The directory doesn't count the link to itself
+                    continue;
+                }
+                b".." => {
+                    has_dotdot = true;
+                    let mut target = target;
+                    if target != parent {
+                        self.problems.push(Problem::BadDotDot { dir: inum, parent });
+                        if self.repair {
+                            self.set_dirent(&mut dir, entry_off, dirent, parent);
+                            dirty = true;
+                            target = parent;
+                        }
+                    }
+                    if self.is_valid_inum(target) {
+                        self.refs[target as usize] += 1;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            if dirent.name().is_empty() || !self.is_valid_inum(target) {
+                self.problems.push(Problem::BadDirent {
+                    dir: inum,
+                    off: entry_off,
+                    inum: target,
+                });
+                if self.repair {
+                    self.set_dirent(&mut dir, entry_off, dirent, 0);
+                    dirty = true;
+                }
+                continue;
+            }
+
+            self.refs[target as usize] += 1;
+            // Only the first link to a directory is followed
+            if self.types[target as usize] == T_DIR && self.refs[target as usize] == 1 {
+                subdirs.push(target);
+            }
+        }
+
+        if !has_dot {
+            self.problems.push(Problem::BadDot { dir: inum });
+            if self.repair {
+                dirty |= self.fs.dirlink(&mut dir, ".", inum);
+            }
+        }
+        if !has_dotdot {
+            self.problems.push(Problem::BadDotDot { dir: inum, parent });
+            if self.repair && self.fs.dirlink(&mut dir, "..", parent) {
+                self.refs[parent as usize] += 1;
+                dirty = true;
+            }
+        }
+
+        if dirty {
+            self.fs.write_inode(inum, &dir);
+        }
+
+        subdirs
+    }
+
+    // Pass 3: walk through the directory tree from root
+    fn check_tree(&mut self) -> bool {
+        if self.types[ROOTINO as usize] != T_DIR {
+            self.problems.push(Problem::BadRoot);
+            return false;
+        }
+
+        let mut stack = vec![(ROOTINO, ROOTINO)];
+        while let Some((inum, parent)) = stack.pop() {
+            for subdir in self.check_dir(inum, parent) {
+                stack.push((subdir, inum));
+            }
+        }
+
+        true
+    }
+
+    // Pass 4: compare the link counts
+    fn check_links(&mut self) {
+        for inum in 1..=self.fs.sb().ninodes {
+            if self.types[inum as usize] == 0 {
+                continue;
+            }
+
+            let mut inode = self.fs.read_inode(inum);
+            let refs = self.refs[inum as usize];
+            if refs == 0 {
+                self.problems.push(Problem::Orphan { inum });
+                if self.repair {
+                    self.fs.free_inode(inum, &mut inode);
+                }
+            } else if inode.nlink as u32 != refs {
+                let nlink = inode.nlink;
+                self.problems.push(Problem::LinkCount { inum, nlink, refs });
+                if self.repair {
+                    inode.nlink = refs as u16;
+                    self.fs.write_inode(inum, &inode);
+                }
+            }
+        }
+    }
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    /* Check the consistency of filesystem and return the problems found.
+     * If `repair` is true, the problems are fixed on the way. The orphans
+     * are released with their blocks. */
+    pub fn check(&self, repair: bool) -> Vec<Problem> {
+        let mut checker = Checker::new(self, repair);
+
+        checker.check_inodes();
+        checker.check_bitmap();
+        if checker.check_tree() {
+            checker.check_links();
+        }
+
+        checker.problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::MemDevice;
+
+    fn new_fs() -> FileSystem<MemDevice> {
+        FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap()
+    }
+
+    // Check the problems, and the filesystem should be clean after repair
+    fn check_and_repair(fs: &FileSystem<MemDevice>, expected: &[Problem]) {
+        assert_eq!(fs.check(false), expected);
+        assert_eq!(fs.check(true), expected);
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn check_clean() {
+        let fs = new_fs();
+        let dir = fs.create(ROOTINO, "dir", T_DIR, 0, 0).unwrap();
+        let file = fs.create(dir, "file", T_FILE, 0, 0).unwrap();
+        let mut inode = fs.read_inode(file);
+        fs.writei(&mut inode, 0, &vec![1; (NDIRECT + 2) * BLKSZ])
+            .unwrap();
+        fs.write_inode(file, &inode);
+
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn check_orphan() {
+        let fs = new_fs();
+        let inum = fs.alloc_inode(T_FILE, 0, 0, 1).unwrap();
+        let mut inode = fs.read_inode(inum);
+        fs.writei(&mut inode, 0, b"data").unwrap();
+        fs.write_inode(inum, &inode);
+        let block_no = inode.directs[0];
+
+        check_and_repair(&fs, &[Problem::Orphan { inum }]);
+        assert!(fs.read_inode(inum).is_free());
+        // The block of orphan is released too
+        assert_eq!(fs.alloc_block(), Some(block_no));
+    }
+
+    #[test]
+    fn check_bitmap() {
+        let fs = new_fs();
+        let leaked = fs.alloc_block().unwrap();
+        let file = fs.create(ROOTINO, "file", T_FILE, 0, 0).unwrap();
+        let mut inode = fs.read_inode(file);
+        fs.writei(&mut inode, 0, b"data").unwrap();
+        fs.write_inode(file, &inode);
+        let unmarked = inode.directs[0];
+        fs.free_block(unmarked);
+
+        check_and_repair(
+            &fs,
+            &[
+                Problem::BlockLeaked { block_no: leaked },
+                Problem::BlockUnmarked { block_no: unmarked },
+            ],
+        );
+    }
+
+    #[test]
+    fn check_blocks() {
+        let fs = new_fs();
+        let a = fs.create(ROOTINO, "a", T_FILE, 0, 0).unwrap();
+        let b = fs.create(ROOTINO, "b", T_FILE, 0, 0).unwrap();
+
+        let mut inode = fs.read_inode(a);
+        fs.writei(&mut inode, 0, b"data").unwrap();
+        fs.write_inode(a, &inode);
+        let block_no = inode.directs[0];
+
+        // Share the block of a, and refer to a metadata block
+        let mut inode = fs.read_inode(b);
+        inode.directs[0] = block_no;
+        inode.directs[1] = 1;
+        inode.size = 2 * BLKSZ as u32;
+        fs.write_inode(b, &inode);
+
+        check_and_repair(
+            &fs,
+            &[
+                Problem::DupBlock {
+                    inum: b,
+                    block_no,
+                    owner: a,
+                },
+                Problem::BadBlock {
+                    inum: b,
+                    block_no: 1,
+                },
+            ],
+        );
+        // The file data is still there, while the bad links are dropped
+        let inode = fs.read_inode(b);
+        assert_eq!(inode.directs[0..2], [0, 0]);
+        assert_eq!(fs.read_inode(a).directs[0], block_no);
+    }
+
+    #[test]
+    fn check_dirents() {
+        let fs = new_fs();
+        let dir = fs.create(ROOTINO, "dir", T_DIR, 0, 0).unwrap();
+        let file = fs.create(dir, "file", T_FILE, 0, 0).unwrap();
+
+        // Break ".." of dir, and make the entry of file dangling
+        let mut inode = fs.read_inode(dir);
+        let off = dirent_len(1);
+        let mut dirent = fs.read_dirent(&inode, off).unwrap();
+        dirent.inum = dir;
+        assert!(fs.write_dirent(&mut inode, off, &dirent));
+        fs.write_inode(dir, &inode);
+        let mut inode = fs.read_inode(file);
+        inode.set_free();
+        fs.write_inode(file, &inode);
+
+        let bad_dotdot = Problem::BadDotDot {
+            dir,
+            parent: ROOTINO,
+        };
+        let bad_dirent = Problem::BadDirent {
+            dir,
+            off: off + dirent_len(2),
+            inum: file,
+        };
+        // The links are counted by the broken ".." without repair
+        let problems = fs.check(false);
+        assert_eq!(problems[0..2], [bad_dotdot, bad_dirent]);
+        assert!(problems.contains(&Problem::LinkCount {
+            inum: ROOTINO,
+            nlink: 2,
+            refs: 1
+        }));
+
+        assert_eq!(fs.check(true), [bad_dotdot, bad_dirent]);
+        assert_eq!(fs.check(false), []);
+        let inode = fs.read_inode(dir);
+        assert_eq!(fs.dirlookup(&inode, ".."), Some(ROOTINO));
+        assert_eq!(fs.dirlookup(&inode, "file"), None);
+    }
+
+    #[test]
+    fn check_corrupted_record() {
+        let fs = new_fs();
+        let a = fs.create(ROOTINO, "a", T_FILE, 0, 0).unwrap();
+        let b = fs.create(ROOTINO, "b", T_FILE, 0, 0).unwrap();
+
+        // Make the record length of "a" too short to hold the entry
+        let mut root = fs.read_inode(ROOTINO);
+        let off = 2 * dirent_len(1);
+        let mut dirent = fs.read_dirent(&root, off).unwrap();
+        dirent.rec_len = 4;
+        assert!(fs.write_dirent(&mut root, off, &dirent));
+
+        check_and_repair(
+            &fs,
+            &[
+                Problem::BadRecord { dir: ROOTINO, off },
+                Problem::Orphan { inum: a },
+                Problem::Orphan { inum: b },
+            ],
+        );
+        // The entry before the corrupted one takes the rest of block
+        let root = fs.read_inode(ROOTINO);
+        let dirent = fs.read_dirent(&root, dirent_len(1)).unwrap();
+        assert_eq!(dirent.name(), b"..");
+        assert_eq!(dirent.rec_len as usize, BLKSZ - dirent_len(1));
+    }
+
+    #[test]
+    fn check_link_count() {
+        let fs = new_fs();
+        let file = fs.create(ROOTINO, "a", T_FILE, 0, 0).unwrap();
+        let mut root = fs.read_inode(ROOTINO);
+        assert!(fs.dirlink(&mut root, "b", file));
+        fs.write_inode(ROOTINO, &root);
+
+        check_and_repair(
+            &fs,
+            &[Problem::LinkCount {
+                inum: file,
+                nlink: 1,
+                refs: 2,
+            }],
+        );
+        assert_eq!(fs.read_inode(file).nlink, 2);
+    }
+
+    #[test]
+    fn check_bad_root() {
+        let fs = new_fs();
+        let mut root = fs.read_inode(ROOTINO);
+        root.typ = T_FILE;
+        fs.write_inode(ROOTINO, &root);
+
+        let problems = fs.check(true);
+        assert_eq!(problems, [Problem::BadRoot]);
+        assert!(!problems[0].repairable());
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(any(test, feature = "std"))]
+use crate::BLKSZ;
+
+/* The storage which the filesystem is built on. It is accessed in the
+ * unit of block, and the buffer is always BLKSZ bytes. */
+pub trait BlockDevice {
+    fn read_block(&self, block_no: u32, buf: &mut [u8]);
+    fn write_block(&self, block_no: u32, buf: &[u8]);
+}
+
+// The image file on host, which is used by the host tools
+#[cfg(feature = "std")]
+pub struct FileDevice {
+    file: std::fs::File,
+}
+
+#[cfg(feature = "std")]
+impl FileDevice {
+    pub fn new(file: std::fs::File) -> Self {
+        FileDevice { file }
+    }
+}
+
+#[cfg(feature = "std")]
+impl BlockDevice for FileDevice {
+    fn read_block(&self, block_no: u32, buf: &mut [u8]) {
+        use std::os::unix::fs::FileExt;
+
+        assert!(buf.len() == BLKSZ);
+        self.file
+            .read_exact_at(buf, block_no as u64 * BLKSZ as u64)
+            .expect("read_block() fail");
+    }
+
+    fn write_block(&self, block_no: u32, buf: &[u8]) {
+        use std::os::unix::fs::FileExt;
+
+        assert!(buf.len() == BLKSZ);
+        self.file
+            .write_all_at(buf, block_no as u64 * BLKSZ as u64)
+            .expect("write_block() fail");
+    }
+}
+
+// The block device on memory for unit test
+#[cfg(test)]
+pub struct MemDevice {
+    blocks: core::cell::RefCell<alloc::vec::Vec<u8>>,
+}
+
+#[cfg(test)]
+impl MemDevice {
+    pub fn new(nblocks: u32) -> Self {
+        MemDevice {
+            blocks: core::cell::RefCell::new(alloc::vec![0; nblocks as usize * BLKSZ]),
+        }
+    }
+}
+
+#[cfg(test)]
+impl BlockDevice for MemDevice {
+    fn read_block(&self, block_no: u32, buf: &mut [u8]) {
+        let start = block_no as usize * BLKSZ;
+        buf.copy_from_slice(&self.blocks.borrow()[start..start + BLKSZ]);
+    }
+
+    fn write_block(&self, block_no: u32, buf: &[u8]) {
+        let start = block_no as usize * BLKSZ;
+        self.blocks.borrow_mut()[start..start + BLKSZ].copy_from_slice(buf);
+    }
+}
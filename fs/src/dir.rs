@@ -0,0 +1,691 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+use crate::device::BlockDevice;
+use crate::error::Error;
+use crate::filesystem::FileSystem;
+use crate::*;
+
+const HEADER_SIZE: usize = size_of::<DirentHeader>();
+
+/* Separate the first path element from the path. The slashes around it
+ * are skipped, so "a//bb/c" gives ("a", "bb/c") and "a/" gives ("a", "").
+ * Return None if there's no element. */
+fn skip_elem(path: &str) -> Option<(&str, &str)> {
+    let path = path.trim_start_matches('/');
+    if path.is_empty() {
+        return None;
+    }
+
+    match path.split_once('/') {
+        Some((name, rest)) => Some((name, rest.trim_start_matches('/'))),
+        None => Some((path, "")),
+    }
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    /* Read the entry at the offset of directory, and the next entry is at
+     * `off + rec_len`. Return None at the end of directory, or if the
+     * entry is corrupted. */
+    pub fn read_dirent(&self, dir: &Inode, off: usize) -> Option<Dirent> {
+        let mut buf = [0; HEADER_SIZE];
+        if self.readi(dir, off, &mut buf) != HEADER_SIZE {
+            return None;
+        }
+
+        let mut header = DirentHeader::default();
+        plain::copy_from_bytes(&mut header, &buf).ok()?;
+
+        let rec_len = header.rec_len as usize;
+        if rec_len < dirent_len(header.name_len as usize)
+            || rec_len % DIRENT_ALIGN != 0
+            || off % BLKSZ + rec_len > BLKSZ
+            || off + rec_len > dir.size as usize
+        {
+            return None;
+        }
+
+        let mut name = [0; NAME_MAX];
+        let name = &mut name[..header.name_len as usize];
+        self.readi(dir, off + HEADER_SIZE, name);
+        Some(Dirent::from_header(&header, name))
+    }
+
+    // Write the entry at the offset of directory
+    pub fn write_dirent(&self, dir: &mut Inode, off: usize, dirent: &Dirent) -> bool {
+        let mut buf = [0; HEADER_SIZE + NAME_MAX];
+        let name = dirent.name();
+        buf[..HEADER_SIZE].copy_from_slice(unsafe { plain::as_bytes(&dirent.header()) });
+        buf[HEADER_SIZE..HEADER_SIZE + name.len()].copy_from_slice(name);
+
+        let len = HEADER_SIZE + name.len();
+        self.writei(dir, off, &buf[..len]) == Some(len)
+    }
+
+    /* Find the entry with the name under the directory. Return its
+     * offset, and the previous entry in the same block if any. */
+    fn find_dirent(&self, dir: &Inode, name: &str) -> Option<(usize, Dirent, Option<usize>)> {
+        assert!(dir.typ == T_DIR);
+
+        let mut off = 0;
+        let mut prev = None;
+        while let Some(dirent) = self.read_dirent(dir, off) {
+            // The unused entry is skipped
+            if dirent.inum != 0 && dirent.name() == name.as_bytes() {
+                return Some((off, dirent, prev));
+            }
+
+            let next = off + dirent.rec_len as usize;
+            prev = if next % BLKSZ == 0 { None } else { Some(off) };
+            off = next;
+        }
+
+        None
+    }
+
+    // Find the inode number of the entry under the directory
+    pub fn dirlookup(&self, dir: &Inode, name: &str) -> Option<u32> {
+        self.find_dirent(dir, name)
+            .map(|(_, dirent, _)| dirent.inum)
+    }
+
+    /* Remove the entry from the directory, and return the inode number
+     * of it. The entry is marked unused, and its space is given to the
+     * previous entry in the same block if any. A reader may still hold
+     * the offset of it, so it must not look like a live entry there. */
+    pub fn dirunlink(&self, dir: &mut Inode, name: &str) -> Option<u32> {
+        let (off, mut dirent, prev) = self.find_dirent(dir, name)?;
+        let inum = dirent.inum;
+
+        dirent.inum = 0;
+        if !self.write_dirent(dir, off, &dirent) {
+            return None;
+        }
+        if let Some(prev_off) = prev {
+            let mut prev = self.read_dirent(dir, prev_off)?;
+            prev.rec_len += dirent.rec_len;
+            if !self.write_dirent(dir, prev_off, &prev) {
+                return None;
+            }
+        }
+
+        Some(inum)
+    }
+
+    /* Get the offset of the first entry at or after `off`. The offset
+     * kept between reads of directory may fall in the middle of an entry
+     * once the directory is changed, so the block is scanned from its
+     * start, as entries never cross blocks. */
+    pub fn dirent_boundary(&self, dir: &Inode, off: usize) -> usize {
+        let mut cur = off - off % BLKSZ;
+        while cur < off {
+            match self.read_dirent(dir, cur) {
+                Some(dirent) => cur += dirent.rec_len as usize,
+                None => return off,
+            }
+        }
+
+        cur
+    }
+
+    /* Make the existing entry refer to another inode, and return the
+     * inode number it referred to. */
+    pub fn dirreplace(&self, dir: &mut Inode, name: &str, inum: u32) -> Option<u32> {
+        let (off, mut dirent, _) = self.find_dirent(dir, name)?;
+        let old = dirent.inum;
+
+        dirent.inum = inum;
+        self.write_dirent(dir, off, &dirent).then_some(old)
+    }
+
+    // Whether the directory contains nothing but "." and ".."
+    pub fn dir_is_empty(&self, dir: &Inode) -> bool {
+        let mut off = 0;
+        while let Some(dirent) = self.read_dirent(dir, off) {
+            let name = dirent.name();
+            if dirent.inum != 0 && name != b"." && name != b".." {
+                return false;
+            }
+            off += dirent.rec_len as usize;
+        }
+
+        true
+    }
+
+    /* Add a new entry to the directory. Return false if the name exists
+     * or is too long. The caller should write the directory inode back
+     * since its size may be changed. */
+    pub fn dirlink(&self, dir: &mut Inode, name: &str, inum: u32) -> bool {
+        if name.is_empty() || self.dirlookup(dir, name).is_some() {
+            return false;
+        }
+
+        let mut new = match Dirent::new(inum, name) {
+            Some(dirent) => dirent,
+            None => return false,
+        };
+        let need = new.rec_len as usize;
+
+        /* Take an unused entry or the space after the name of an entry
+         * if it is large enough. */
+        let mut off = 0;
+        while let Some(mut dirent) = self.read_dirent(dir, off) {
+            let rec_len = dirent.rec_len as usize;
+            if dirent.inum == 0 && rec_len >= need {
+                new.rec_len = dirent.rec_len;
+                return self.write_dirent(dir, off, &new);
+            }
+
+            let used = dirent.used_len();
+            if dirent.inum != 0 && rec_len - used >= need {
+                // Split the entry into two
+                dirent.rec_len = used as u16;
+                new.rec_len = (rec_len - used) as u16;
+                return self.write_dirent(dir, off, &dirent)
+                    && self.write_dirent(dir, off + used, &new);
+            }
+
+            off += rec_len;
+        }
+
+        // The corrupted entry should not be overridden
+        if off != dir.size as usize || off % BLKSZ != 0 {
+            return false;
+        }
+
+        /* Otherwise, append a new block to the directory, and the entry
+         * takes the whole block. */
+        new.rec_len = BLKSZ as u16;
+        let mut buf = vec![0; BLKSZ];
+        buf[..HEADER_SIZE].copy_from_slice(unsafe { plain::as_bytes(&new.header()) });
+        buf[HEADER_SIZE..HEADER_SIZE + name.len()].copy_from_slice(name.as_bytes());
+        self.writei(dir, off, &buf) == Some(BLKSZ)
+    }
+
+    /* Create a new inode with the name under the directory, and return
+     * its inode number. A new directory is linked with "." and "..".
+     * Return None if the name exists or there's no space. */
+    pub fn create(&self, dir: u32, name: &str, typ: u16, major: u16, minor: u16) -> Option<u32> {
+        let mut parent = self.read_inode(dir);
+        if parent.typ != T_DIR || self.dirlookup(&parent, name).is_some() {
+            return None;
+        }
+
+        /* Note that nlink of directory don't cosider itself(".").
+         * The purpose is to get rid of cyclic ref count */
+        let inum = self.alloc_inode(typ, major, minor, 1)?;
+        let mut inode = self.read_inode(inum);
+
+        /* Link this new inode to its parent directory after the "." and
+         * ".." are linked, so we don't need to roll back the parent if
+         * anything fails. */
+        let linked = if typ == T_DIR {
+            self.dirlink(&mut inode, ".", inum) && self.dirlink(&mut inode, "..", dir)
+        } else {
+            true
+        };
+        if !linked || !self.dirlink(&mut parent, name, inum) {
+            self.free_inode(inum, &mut inode);
+            return None;
+        }
+        self.write_inode(inum, &inode);
+
+        // Since parent("..") is linked by the new directory
+        if typ == T_DIR {
+            parent.nlink += 1;
+        }
+        self.write_inode(dir, &parent);
+
+        Some(inum)
+    }
+
+    /* Walk through the path and return the inode number of the last
+     * element. The absolute path starts from root, otherwise it starts
+     * from the directory `cwd`.
+     *
+     * The symbolic links in the middle of path are always followed, and
+     * the last one is followed only if `follow` is true or the path has
+     * trailing slash. The link is expanded in place of the path, so it
+     * doesn't recurse on the small kernel stack. */
+    fn namex(&self, cwd: u32, path: &str, follow: bool) -> Result<u32, Error> {
+        if path.is_empty() {
+            return Err(Error::NotFound);
+        }
+
+        let mut inum = if path.starts_with('/') { ROOTINO } else { cwd };
+        // The path with trailing slash should end with a directory
+        let dir_only = path.ends_with('/');
+        let follow = follow || dir_only;
+        let mut path = Cow::Borrowed(path);
+        let mut off = 0;
+        let mut nlinks = 0;
+        while let Some((name, rest)) = skip_elem(&path[off..]) {
+            /* This inode is corresponded to a directory, but we want to find
+             * a file under it. This is an invalid request. */
+            let inode = self.read_inode(inum);
+            if inode.typ != T_DIR {
+                return Err(Error::NotDir);
+            }
+
+            let next = self.dirlookup(&inode, name).ok_or(Error::NotFound)?;
+            let next_inode = self.read_inode(next);
+            if next_inode.typ == T_SYMLINK && (follow || !rest.is_empty()) {
+                nlinks += 1;
+                if nlinks > MAXSYMLINKS {
+                    return Err(Error::Loop);
+                }
+
+                // The relative target starts from the directory of link
+                let target = self
+                    .read_symlink(&next_inode)
+                    .filter(|target| !target.is_empty())
+                    .ok_or(Error::NotFound)?;
+                if target.starts_with('/') {
+                    inum = ROOTINO;
+                }
+                path = Cow::Owned(target + "/" + rest);
+                off = 0;
+                continue;
+            }
+
+            inum = next;
+            off = path.len() - rest.len();
+        }
+
+        if dir_only && self.read_inode(inum).typ != T_DIR {
+            return Err(Error::NotDir);
+        }
+
+        Ok(inum)
+    }
+
+    /* Find the inode number by the path. The last symbolic link is
+     * followed if `follow` is true. */
+    pub fn lookup(&self, cwd: u32, path: &str, follow: bool) -> Result<u32, Error> {
+        self.namex(cwd, path, follow)
+    }
+
+    /* Find the inode number of the parent directory by the path, with
+     * the name of the last element, which is never followed. */
+    pub fn lookup_parent<'a>(&self, cwd: u32, path: &'a str) -> Result<(u32, &'a str), Error> {
+        let (dir, name) = match path.trim_end_matches('/').rsplit_once('/') {
+            Some((dir, name)) => (dir, name),
+            None => ("", path.trim_end_matches('/')),
+        };
+        // There's no last element for the parent, e.g. "/"
+        if name.is_empty() {
+            return Err(Error::NotFound);
+        }
+
+        let inum = match dir {
+            "" if path.starts_with('/') => ROOTINO,
+            "" => cwd,
+            dir => self.namex(cwd, dir, true)?,
+        };
+        if self.read_inode(inum).typ != T_DIR {
+            return Err(Error::NotDir);
+        }
+
+        Ok((inum, name))
+    }
+
+    // Find the inode number by the path, and the last link is followed
+    pub fn namei(&self, cwd: u32, path: &str) -> Option<u32> {
+        self.lookup(cwd, path, true).ok()
+    }
+
+    /* Find the inode number of the parent directory by the path, with
+     * the name of the last element. */
+    pub fn nameiparent<'a>(&self, cwd: u32, path: &'a str) -> Option<(u32, &'a str)> {
+        self.lookup_parent(cwd, path).ok()
+    }
+
+    // Read the target path of the symbolic link
+    pub fn read_symlink(&self, inode: &Inode) -> Option<String> {
+        if inode.typ != T_SYMLINK || inode.size as usize > SYMLINK_MAX {
+            return None;
+        }
+
+        let mut buf = vec![0; inode.size as usize];
+        if self.readi(inode, 0, &mut buf) != buf.len() {
+            return None;
+        }
+        String::from_utf8(buf).ok()
+    }
+
+    // Find the name of the entry to the inode under the directory
+    fn dirname(&self, dir: &Inode, inum: u32) -> Option<String> {
+        let mut off = 0;
+        while let Some(dirent) = self.read_dirent(dir, off) {
+            let name = dirent.name();
+            if dirent.inum == inum && name != b"." && name != b".." {
+                return Some(String::from_utf8_lossy(name).into_owned());
+            }
+            off += dirent.rec_len as usize;
+        }
+
+        None
+    }
+
+    /* Get the absolute path of the directory. It walks up to root through
+     * "..", and finds the name of each directory in its parent. */
+    pub fn dirpath(&self, mut inum: u32) -> Option<String> {
+        let mut names = Vec::new();
+
+        while inum != ROOTINO {
+            // The depth can't exceed the number of inodes without a loop
+            if names.len() >= self.sb().ninodes as usize {
+                return None;
+            }
+
+            let dir = self.read_inode(inum);
+            if dir.typ != T_DIR {
+                return None;
+            }
+
+            let parent = self.dirlookup(&dir, "..")?;
+            names.push(self.dirname(&self.read_inode(parent), inum)?);
+            inum = parent;
+        }
+
+        if names.is_empty() {
+            return Some(String::from("/"));
+        }
+
+        let mut path = String::new();
+        for name in names.iter().rev() {
+            path.push('/');
+            path.push_str(name);
+        }
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::MemDevice;
+
+    fn create(fs: &FileSystem<MemDevice>, dir: u32, name: &str, typ: u16) -> u32 {
+        fs.create(dir, name, typ, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn skip_path_elements() {
+        assert_eq!(skip_elem("a/bb/c"), Some(("a", "bb/c")));
+        assert_eq!(skip_elem("///a//bb"), Some(("a", "bb")));
+        assert_eq!(skip_elem("a/"), Some(("a", "")));
+        assert_eq!(skip_elem("a"), Some(("a", "")));
+        assert_eq!(skip_elem(""), None);
+        assert_eq!(skip_elem("///"), None);
+    }
+
+    #[test]
+    fn lookup_root_entries() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let root = fs.read_inode(ROOTINO);
+
+        assert_eq!(fs.dirlookup(&root, "."), Some(ROOTINO));
+        assert_eq!(fs.dirlookup(&root, ".."), Some(ROOTINO));
+        assert_eq!(fs.dirlookup(&root, "missing"), None);
+    }
+
+    #[test]
+    fn link_duplicated_or_long_name() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut root = fs.read_inode(ROOTINO);
+
+        assert!(!fs.dirlink(&mut root, ".", ROOTINO));
+        assert!(!fs.dirlink(&mut root, &"x".repeat(NAME_MAX + 1), ROOTINO));
+        assert!(!fs.dirlink(&mut root, "", ROOTINO));
+    }
+
+    #[test]
+    fn link_long_names() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut root = fs.read_inode(ROOTINO);
+
+        // Fill more than one block with the longest names
+        let names: Vec<_> = (0..BLKSZ / NAME_MAX * 2)
+            .map(|i| format!("{:0>width$}", i, width = NAME_MAX))
+            .collect();
+        for name in &names {
+            assert!(fs.dirlink(&mut root, name, ROOTINO));
+        }
+        assert!(root.size as usize > BLKSZ);
+        assert_eq!(root.size as usize % BLKSZ, 0);
+
+        for name in &names {
+            assert_eq!(fs.dirlookup(&root, name), Some(ROOTINO));
+        }
+    }
+
+    #[test]
+    fn link_large_inum() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut root = fs.read_inode(ROOTINO);
+
+        // The inode number beyond 16 bits should be kept
+        let inum = u16::MAX as u32 + 10;
+        assert!(fs.dirlink(&mut root, "large", inum));
+        assert_eq!(fs.dirlookup(&root, "large"), Some(inum));
+    }
+
+    #[test]
+    fn unlink_entries() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut root = fs.read_inode(ROOTINO);
+        assert!(fs.dir_is_empty(&root));
+
+        for (i, name) in ["a", "b", "c"].iter().enumerate() {
+            assert!(fs.dirlink(&mut root, name, i as u32 + 10));
+        }
+        assert!(!fs.dir_is_empty(&root));
+
+        // Remove the one in the middle, and its space is merged to "a"
+        assert_eq!(fs.dirunlink(&mut root, "b"), Some(11));
+        assert_eq!(fs.dirunlink(&mut root, "b"), None);
+        assert_eq!(fs.dirlookup(&root, "a"), Some(10));
+        assert_eq!(fs.dirlookup(&root, "c"), Some(12));
+        let off = 2 * dirent_len(1);
+        assert_eq!(
+            fs.read_dirent(&root, off).unwrap().rec_len as usize,
+            2 * dirent_len(1)
+        );
+
+        assert_eq!(fs.dirreplace(&mut root, "c", 20), Some(12));
+        assert_eq!(fs.dirlookup(&root, "c"), Some(20));
+
+        assert_eq!(fs.dirunlink(&mut root, "a"), Some(10));
+        assert_eq!(fs.dirunlink(&mut root, "c"), Some(20));
+        assert!(fs.dir_is_empty(&root));
+    }
+
+    #[test]
+    fn unlink_first_entry_of_block() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut root = fs.read_inode(ROOTINO);
+
+        // The last entry of the longest name can't fit in the first block
+        let names: Vec<_> = (0..BLKSZ / NAME_MAX)
+            .map(|i| format!("{:0>width$}", i, width = NAME_MAX))
+            .collect();
+        for name in &names {
+            assert!(fs.dirlink(&mut root, name, ROOTINO));
+        }
+        let last = names.last().unwrap();
+        assert_eq!(
+            fs.read_dirent(&root, BLKSZ).unwrap().name(),
+            last.as_bytes()
+        );
+
+        // The first entry in the second block is marked unused
+        assert_eq!(fs.dirunlink(&mut root, last), Some(ROOTINO));
+        let dirent = fs.read_dirent(&root, BLKSZ).unwrap();
+        assert_eq!(dirent.inum, 0);
+        assert_eq!(dirent.rec_len as usize, BLKSZ);
+        assert!(fs.dirlink(&mut root, last, ROOTINO));
+        assert_eq!(fs.read_dirent(&root, BLKSZ).unwrap().inum, ROOTINO);
+        assert_eq!(root.size as usize, 2 * BLKSZ);
+    }
+
+    #[test]
+    fn resume_listing_after_unlink() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut root = fs.read_inode(ROOTINO);
+        for (i, name) in ["a", "b", "c", "d"].iter().enumerate() {
+            assert!(fs.dirlink(&mut root, name, i as u32 + 10));
+        }
+
+        // List the entries from the offset, like getdents does
+        let list = |root: &Inode, off: usize| {
+            let mut off = fs.dirent_boundary(root, off);
+            let mut names = Vec::new();
+            while let Some(dirent) = fs.read_dirent(root, off) {
+                if dirent.inum != 0 {
+                    names.push(String::from_utf8(dirent.name().to_vec()).unwrap());
+                }
+                off += dirent.rec_len as usize;
+            }
+            names
+        };
+
+        // Stop listing before "b", and the removed one is not listed later
+        let b = 3 * dirent_len(1);
+        let c = 4 * dirent_len(1);
+        assert_eq!(list(&root, b), ["b", "c", "d"]);
+        assert_eq!(fs.dirunlink(&mut root, "b"), Some(11));
+        assert_eq!(list(&root, b), ["c", "d"]);
+
+        // A new entry may now cover the offset of "c", which is gone too
+        assert_eq!(fs.dirunlink(&mut root, "c"), Some(12));
+        assert!(fs.dirlink(&mut root, &"e".repeat(dirent_len(1)), 14));
+        assert_eq!(fs.dirlookup(&root, "d"), Some(13));
+        assert_eq!(list(&root, c), ["d"]);
+    }
+
+    #[test]
+    fn link_reuses_empty_entry() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let a = create(&fs, ROOTINO, "a", T_FILE);
+        let mut root = fs.read_inode(ROOTINO);
+        let size = root.size;
+
+        // Clear the entry of "a" by hand, then link another one
+        let off = 2 * dirent_len(1);
+        let mut dirent = fs.read_dirent(&root, off).unwrap();
+        assert_eq!(dirent.name(), b"a");
+        dirent.inum = 0;
+        assert!(fs.write_dirent(&mut root, off, &dirent));
+        assert!(fs.dirlink(&mut root, "b", a));
+
+        assert_eq!(root.size, size);
+        assert_eq!(fs.read_dirent(&root, off).unwrap().name(), b"b");
+        assert_eq!(fs.dirlookup(&root, "a"), None);
+        assert_eq!(fs.dirlookup(&root, "b"), Some(a));
+    }
+
+    #[test]
+    fn create_directory() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let nlink = fs.read_inode(ROOTINO).nlink;
+        let dir = create(&fs, ROOTINO, "dir", T_DIR);
+
+        let inode = fs.read_inode(dir);
+        assert_eq!(fs.dirlookup(&inode, "."), Some(dir));
+        assert_eq!(fs.dirlookup(&inode, ".."), Some(ROOTINO));
+        assert_eq!(fs.read_inode(ROOTINO).nlink, nlink + 1);
+
+        // The name should be unique and the parent should be a directory
+        assert_eq!(fs.create(ROOTINO, "dir", T_FILE, 0, 0), None);
+        let file = create(&fs, dir, "file", T_FILE);
+        assert_eq!(fs.create(file, "x", T_FILE, 0, 0), None);
+    }
+
+    #[test]
+    fn create_failure_frees_inode() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let long_name = "x".repeat(NAME_MAX + 1);
+        assert_eq!(fs.create(ROOTINO, &long_name, T_FILE, 0, 0), None);
+
+        // The inode allocated for the failed one should be released
+        let inum = fs.alloc_inode(T_FILE, 0, 0, 1).unwrap();
+        assert_eq!(fs.create(ROOTINO, "a", T_FILE, 0, 0), Some(inum + 1));
+    }
+
+    #[test]
+    fn resolve_path() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let dir = create(&fs, ROOTINO, "dir", T_DIR);
+        let file = create(&fs, dir, "file", T_FILE);
+
+        assert_eq!(fs.namei(ROOTINO, "/"), Some(ROOTINO));
+        assert_eq!(fs.namei(ROOTINO, "/dir"), Some(dir));
+        assert_eq!(fs.namei(ROOTINO, "//dir//file"), Some(file));
+        assert_eq!(fs.namei(ROOTINO, "/dir/"), Some(dir));
+        assert_eq!(fs.namei(ROOTINO, "/dir/../dir/./file"), Some(file));
+        assert_eq!(fs.namei(ROOTINO, "/.."), Some(ROOTINO));
+        assert_eq!(fs.namei(ROOTINO, "/dir/missing"), None);
+        assert_eq!(fs.namei(ROOTINO, ""), None);
+        // A file can't be the middle of path, or end with slash
+        assert_eq!(fs.namei(ROOTINO, "/dir/file/x"), None);
+        assert_eq!(fs.namei(ROOTINO, "/dir/file/"), None);
+    }
+
+    #[test]
+    fn name_length_limit() {
+        assert!(!name_too_long(&"x".repeat(NAME_MAX)));
+        assert!(name_too_long(&format!("/a/{}/b", "x".repeat(NAME_MAX + 1))));
+    }
+
+    #[test]
+    fn resolve_relative_path() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let dir = create(&fs, ROOTINO, "dir", T_DIR);
+        let sub = create(&fs, dir, "sub", T_DIR);
+        let file = create(&fs, dir, "file", T_FILE);
+
+        assert_eq!(fs.namei(ROOTINO, "dir"), Some(dir));
+        assert_eq!(fs.namei(dir, "file"), Some(file));
+        assert_eq!(fs.namei(dir, "."), Some(dir));
+        assert_eq!(fs.namei(dir, "./sub/"), Some(sub));
+        assert_eq!(fs.namei(sub, ".."), Some(dir));
+        assert_eq!(fs.namei(sub, "../file"), Some(file));
+        assert_eq!(fs.namei(sub, "../../dir/sub"), Some(sub));
+        // The absolute path doesn't depend on the cwd
+        assert_eq!(fs.namei(sub, "/dir"), Some(dir));
+        // The cwd should be a directory
+        assert_eq!(fs.namei(file, "x"), None);
+    }
+
+    #[test]
+    fn resolve_parent() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let dir = create(&fs, ROOTINO, "dir", T_DIR);
+
+        assert_eq!(fs.nameiparent(ROOTINO, "/dir/new"), Some((dir, "new")));
+        assert_eq!(fs.nameiparent(ROOTINO, "/new"), Some((ROOTINO, "new")));
+        assert_eq!(fs.nameiparent(ROOTINO, "/dir/new/"), Some((dir, "new")));
+        assert_eq!(fs.nameiparent(dir, "new"), Some((dir, "new")));
+        assert_eq!(fs.nameiparent(dir, "../new"), Some((ROOTINO, "new")));
+        assert_eq!(fs.nameiparent(ROOTINO, "/"), None);
+        // The parent should exist even the last element doesn't
+        assert_eq!(fs.nameiparent(ROOTINO, "/missing/new"), None);
+    }
+
+    #[test]
+    fn directory_path() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let dir = create(&fs, ROOTINO, "dir", T_DIR);
+        let sub = create(&fs, dir, "sub", T_DIR);
+        let file = create(&fs, dir, "file", T_FILE);
+
+        assert_eq!(fs.dirpath(ROOTINO).as_deref(), Some("/"));
+        assert_eq!(fs.dirpath(dir).as_deref(), Some("/dir"));
+        assert_eq!(fs.dirpath(sub).as_deref(), Some("/dir/sub"));
+        assert_eq!(fs.dirpath(file), None);
+    }
+}
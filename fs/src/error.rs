@@ -0,0 +1,50 @@
+use core::fmt;
+
+// The errors of filesystem operations, which are mapped to errno by kernel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    // No such file or directory
+    NotFound,
+    // The file exists already
+    Exists,
+    // A directory is expected
+    NotDir,
+    // The operation is not allowed on a directory
+    IsDir,
+    // The directory is not empty
+    NotEmpty,
+    // The name or the path is too long
+    NameTooLong,
+    // The argument is invalid, e.g. moving a directory under itself
+    Invalid,
+    // The file is in use by the system, e.g. the root directory
+    Busy,
+    // The operation is not permitted, e.g. hard link to a directory
+    NotPermitted,
+    // The file has too many links
+    TooManyLinks,
+    // There's no inode or block left
+    NoSpace,
+    // Too many symbolic links are followed, which may be a loop
+    Loop,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Error::NotFound => "no such file or directory",
+            Error::Exists => "file exists",
+            Error::NotDir => "not a directory",
+            Error::IsDir => "is a directory",
+            Error::NotEmpty => "directory not empty",
+            Error::NameTooLong => "file name too long",
+            Error::Invalid => "invalid argument",
+            Error::Busy => "resource busy",
+            Error::NotPermitted => "operation not permitted",
+            Error::TooManyLinks => "too many links",
+            Error::NoSpace => "no space left on device",
+            Error::Loop => "too many levels of symbolic links",
+        };
+        write!(f, "{}", msg)
+    }
+}
@@ -0,0 +1,449 @@
+use alloc::vec;
+use core::fmt;
+use core::mem::size_of;
+
+use crate::device::BlockDevice;
+use crate::*;
+
+// Block 1 is where the SuperBlock located at
+const SB_BLOCK: u32 = 1;
+
+// The reason why the filesystem can't be opened
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenError {
+    // Not a filesystem at all
+    BadMagic,
+    // The image is made with another revision of on-disk format
+    BadVersion(u32),
+    // The layout is inconsistent with the geometry it records
+    BadLayout,
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OpenError::BadMagic => write!(f, "bad magic number"),
+            OpenError::BadVersion(version) => write!(
+                f,
+                "unsupported version {} (expected {}), please recreate the image with mkfs",
+                version, FS_VERSION
+            ),
+            OpenError::BadLayout => write!(f, "inconsistent layout in superblock"),
+        }
+    }
+}
+
+/* The filesystem on a block device. All the operations on disk are done
+ * through the device, so the same implementation is shared by the kernel
+ * and the host tools. */
+pub struct FileSystem<D: BlockDevice> {
+    dev: D,
+    sb: SuperBlock,
+    // The source of timestamps, in nanoseconds since epoch
+    clock: fn() -> u64,
+    // Whether the inode is still opened, so it can't be released yet
+    is_open: fn(u32) -> bool,
+}
+
+// The host tools never keep an inode open across operations
+fn never_open(_inum: u32) -> bool {
+    false
+}
+
+#[cfg(any(test, feature = "std"))]
+fn default_clock() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+// There's no clock without std, so the kernel should provide one
+#[cfg(not(any(test, feature = "std")))]
+fn default_clock() -> u64 {
+    0
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    /* Open the filesystem on the device. The image of old version is
+     * refused, since it can't be read correctly. */
+    pub fn open(dev: D) -> Result<Self, OpenError> {
+        let mut buf = vec![0; BLKSZ];
+        dev.read_block(SB_BLOCK, &mut buf);
+
+        let mut sb = SuperBlock::default();
+        plain::copy_from_bytes(&mut sb, &buf).map_err(|_| OpenError::BadMagic)?;
+        if sb.magic != MAGIC {
+            return Err(OpenError::BadMagic);
+        }
+        if sb.version != FS_VERSION {
+            return Err(OpenError::BadVersion(sb.version));
+        }
+        if SuperBlock::new(&sb.geometry()) != Some(sb) {
+            return Err(OpenError::BadLayout);
+        }
+
+        Ok(FileSystem {
+            dev,
+            sb,
+            clock: default_clock,
+            is_open: never_open,
+        })
+    }
+
+    /* Create an empty filesystem with the geometry on the device, which
+     * only contains the root directory. See SuperBlock::new for the
+     * layout. Return None if the geometry is invalid. */
+    pub fn format(dev: D, geo: &Geometry) -> Option<Self> {
+        assert!(BLKSZ % size_of::<Inode>() == 0);
+
+        let sb = SuperBlock::new(geo)?;
+
+        let mut buf = vec![0; BLKSZ];
+        for i in 0..sb.fs_blksz {
+            dev.write_block(i, &buf);
+        }
+
+        buf[0..size_of::<SuperBlock>()].copy_from_slice(unsafe { plain::as_bytes(&sb) });
+        dev.write_block(SB_BLOCK, &buf);
+
+        let fs = FileSystem {
+            dev,
+            sb,
+            clock: default_clock,
+            is_open: never_open,
+        };
+
+        // The metadata blocks are always in use
+        for block_no in 0..sb.nmeta() {
+            fs.set_bitmap(block_no, true);
+        }
+
+        /* Create inode for the root directory, with the entries of itself
+         * and its parent, which are both the root. */
+        let rootino = fs.alloc_inode(T_DIR, 0, 0, 1).unwrap();
+        assert!(rootino == ROOTINO);
+
+        let mut root = fs.read_inode(rootino);
+        assert!(fs.dirlink(&mut root, ".", rootino));
+        assert!(fs.dirlink(&mut root, "..", rootino));
+        fs.write_inode(rootino, &root);
+
+        Some(fs)
+    }
+
+    // Use the clock for the timestamps of inodes
+    pub fn with_clock(mut self, clock: fn() -> u64) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /* Check whether the inode is opened before it is released, and the
+     * inode which is still opened is kept without any link. The caller
+     * should release it by `release_orphan` after it's closed. */
+    pub fn with_open_check(mut self, is_open: fn(u32) -> bool) -> Self {
+        self.is_open = is_open;
+        self
+    }
+
+    pub(crate) fn is_open(&self, inum: u32) -> bool {
+        (self.is_open)(inum)
+    }
+
+    // The current time in nanoseconds since epoch
+    pub fn now(&self) -> u64 {
+        (self.clock)()
+    }
+
+    pub fn sb(&self) -> &SuperBlock {
+        &self.sb
+    }
+
+    pub fn device(&self) -> &D {
+        &self.dev
+    }
+
+    pub fn read_inode(&self, inum: u32) -> Inode {
+        let mut buf = vec![0; BLKSZ];
+        self.dev.read_block(iblock(&self.sb, inum), &mut buf);
+        *block_inode(&mut buf, inum)
+    }
+
+    pub fn write_inode(&self, inum: u32, inode: &Inode) {
+        // Read, modify, and write back the block which contains the inode
+        let mut buf = vec![0; BLKSZ];
+        let block_no = iblock(&self.sb, inum);
+        self.dev.read_block(block_no, &mut buf);
+        *block_inode(&mut buf, inum) = *inode;
+        self.dev.write_block(block_no, &buf);
+    }
+
+    /* Linear checking every inode for the one which is marked as
+     * non-allocated. Return None if there's no free inode. */
+    pub fn alloc_inode(&self, typ: u16, major: u16, minor: u16, nlink: u16) -> Option<u32> {
+        let mut buf = vec![0; BLKSZ];
+        let mut cur_block = 0;
+
+        for inum in 1..=self.sb.ninodes {
+            let block_no = iblock(&self.sb, inum);
+            if block_no != cur_block {
+                self.dev.read_block(block_no, &mut buf);
+                cur_block = block_no;
+            }
+
+            let inode = block_inode(&mut buf, inum);
+            if inode.is_free() {
+                inode.init(typ, major, minor, nlink);
+                let now = self.now();
+                (inode.atime, inode.mtime, inode.ctime) = (now, now, now);
+                self.dev.write_block(block_no, &buf);
+                return Some(inum);
+            }
+        }
+
+        None
+    }
+
+    // Release the data blocks of inode and mark itself as free
+    pub fn free_inode(&self, inum: u32, inode: &mut Inode) {
+        self.itrunc(inode);
+        inode.set_free();
+        self.write_inode(inum, inode);
+    }
+
+    /* Release the inode which has no link left, after the last open file
+     * of it is closed. Return false if it is still linked. */
+    pub fn release_orphan(&self, inum: u32) -> bool {
+        let mut inode = self.read_inode(inum);
+        if inode.is_free() || inode.nlink > 0 {
+            return false;
+        }
+
+        self.free_inode(inum, &mut inode);
+        true
+    }
+
+    // Set or clear the bit of the block in bitmap
+    pub(crate) fn set_bitmap(&self, block_no: u32, used: bool) {
+        let mut buf = vec![0; BLKSZ];
+        let bmap_block = block_bmap(&self.sb, block_no);
+        self.dev.read_block(bmap_block, &mut buf);
+
+        let bit = block_no as usize % BIT_PER_BLK;
+        let mask = 1 << (bit % 8);
+        if used {
+            buf[bit / 8] |= mask;
+        } else {
+            buf[bit / 8] &= !mask;
+        }
+        self.dev.write_block(bmap_block, &buf);
+    }
+
+    /* Linear checking every bit in bitmap for the block which is
+     * marked as non-allocated. The block is zeroed before return. */
+    pub fn alloc_block(&self) -> Option<u32> {
+        let mut buf = vec![0; BLKSZ];
+
+        for base in (0..self.sb.fs_blksz).step_by(BIT_PER_BLK) {
+            let bmap_block = block_bmap(&self.sb, base);
+            self.dev.read_block(bmap_block, &mut buf);
+
+            let nbits = (self.sb.fs_blksz - base).min(BIT_PER_BLK as u32);
+            for bit in 0..nbits as usize {
+                let mask = 1 << (bit % 8);
+                if buf[bit / 8] & mask == 0 {
+                    buf[bit / 8] |= mask;
+                    self.dev.write_block(bmap_block, &buf);
+
+                    let block_no = base + bit as u32;
+                    self.dev.write_block(block_no, &vec![0; BLKSZ]);
+                    return Some(block_no);
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn free_block(&self, block_no: u32) {
+        self.set_bitmap(block_no, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::MemDevice;
+
+    #[test]
+    fn format_and_open() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let fs = FileSystem::open(fs.dev).unwrap();
+        assert_eq!(fs.sb().fs_blksz, FS_BLKSZ);
+        assert_eq!(fs.sb().version, FS_VERSION);
+
+        let root = fs.read_inode(ROOTINO);
+        assert_eq!(root.typ, T_DIR);
+    }
+
+    #[test]
+    fn open_invalid() {
+        let result = FileSystem::open(MemDevice::new(FS_BLKSZ));
+        assert_eq!(result.err(), Some(OpenError::BadMagic));
+    }
+
+    #[test]
+    fn inode_alloc_and_free() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+
+        let a = fs.alloc_inode(T_FILE, 0, 0, 1).unwrap();
+        let b = fs.alloc_inode(T_FILE, 0, 0, 1).unwrap();
+        assert_ne!(a, b);
+
+        let mut inode = fs.read_inode(a);
+        fs.free_inode(a, &mut inode);
+        assert_eq!(fs.alloc_inode(T_FILE, 0, 0, 1), Some(a));
+    }
+
+    #[test]
+    fn inode_exhausted() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        // The root directory takes one of them
+        for _ in 1..NINODES {
+            assert!(fs.alloc_inode(T_FILE, 0, 0, 1).is_some());
+        }
+        assert!(fs.alloc_inode(T_FILE, 0, 0, 1).is_none());
+    }
+
+    #[test]
+    fn block_alloc_and_free() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+
+        let a = fs.alloc_block().unwrap();
+        // The metadata blocks should never be allocated
+        assert!(a >= fs.sb().nmeta());
+        let b = fs.alloc_block().unwrap();
+        assert_ne!(a, b);
+
+        fs.free_block(a);
+        assert_eq!(fs.alloc_block(), Some(a));
+    }
+
+    #[test]
+    fn format_with_geometry() {
+        let geo = Geometry {
+            fs_blksz: 500,
+            ninodes: 50,
+            nlog: 5,
+        };
+        let fs = FileSystem::format(MemDevice::new(geo.fs_blksz), &geo).unwrap();
+        let fs = FileSystem::open(fs.dev).unwrap();
+
+        let sb = fs.sb();
+        assert_eq!((sb.fs_blksz, sb.ninodes, sb.nlog), (500, 50, 5));
+        assert_eq!(sb.inodestart, 2 + 5);
+        assert_eq!(sb.bmapstart, sb.inodestart + sb.inode_blksz());
+        assert_eq!(sb.nblocks + sb.nmeta(), sb.fs_blksz);
+
+        for _ in 1..geo.ninodes {
+            assert!(fs.alloc_inode(T_FILE, 0, 0, 1).is_some());
+        }
+        assert!(fs.alloc_inode(T_FILE, 0, 0, 1).is_none());
+    }
+
+    #[test]
+    fn format_many_inodes() {
+        let geo = Geometry {
+            fs_blksz: 10000,
+            ninodes: u16::MAX as u32 + 100,
+            ..Geometry::default()
+        };
+        let fs = FileSystem::format(MemDevice::new(geo.fs_blksz), &geo).unwrap();
+
+        // The inode beyond 16 bits can be linked to the directory
+        let inum = geo.ninodes;
+        let mut inode = fs.read_inode(inum);
+        inode.init(T_FILE, 0, 0, 1);
+        fs.write_inode(inum, &inode);
+        let mut root = fs.read_inode(ROOTINO);
+        assert!(fs.dirlink(&mut root, "file", inum));
+        fs.write_inode(ROOTINO, &root);
+
+        assert_eq!(fs.namei(ROOTINO, "/file"), Some(inum));
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn format_invalid_geometry() {
+        let dev = || MemDevice::new(FS_BLKSZ);
+        let geo = Geometry::default();
+
+        let no_inode = Geometry { ninodes: 0, ..geo };
+        assert!(FileSystem::format(dev(), &no_inode).is_none());
+        let too_many_inodes = Geometry {
+            ninodes: 1 << 16,
+            ..geo
+        };
+        assert!(FileSystem::format(dev(), &too_many_inodes).is_none());
+        // No space left for data block
+        let no_data = Geometry {
+            nlog: FS_BLKSZ,
+            ..geo
+        };
+        assert!(FileSystem::format(dev(), &no_data).is_none());
+        let overflow = Geometry {
+            nlog: u32::MAX,
+            ..geo
+        };
+        assert!(FileSystem::format(dev(), &overflow).is_none());
+    }
+
+    // Modify the superblock on disk, and try to open it again
+    fn open_modified(modify: impl FnOnce(&mut SuperBlock)) -> Option<OpenError> {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+
+        let mut sb = *fs.sb();
+        modify(&mut sb);
+        let mut buf = vec![0; BLKSZ];
+        buf[0..size_of::<SuperBlock>()].copy_from_slice(unsafe { plain::as_bytes(&sb) });
+        fs.dev.write_block(SB_BLOCK, &buf);
+
+        FileSystem::open(fs.dev).err()
+    }
+
+    #[test]
+    fn open_inconsistent_superblock() {
+        let result = open_modified(|sb| sb.bmapstart += 1);
+        assert_eq!(result, Some(OpenError::BadLayout));
+    }
+
+    #[test]
+    fn open_old_version() {
+        // The image before versioning reads 0
+        let result = open_modified(|sb| sb.version = 0);
+        assert_eq!(result, Some(OpenError::BadVersion(0)));
+    }
+
+    #[test]
+    fn block_alloc_multiple_bitmap() {
+        let geo = Geometry {
+            fs_blksz: BIT_PER_BLK as u32 + 100,
+            ..Geometry::default()
+        };
+        let fs = FileSystem::format(MemDevice::new(geo.fs_blksz), &geo).unwrap();
+        assert_eq!(fs.sb().bitmap_blksz(), 2);
+
+        /* All of the data blocks except the one of root directory are
+         * available, including those described by the second bitmap block */
+        let mut last = 0;
+        for _ in 1..fs.sb().nblocks {
+            last = fs.alloc_block().unwrap();
+        }
+        assert_eq!(last, geo.fs_blksz - 1);
+        assert!(fs.alloc_block().is_none());
+
+        fs.free_block(last);
+        assert_eq!(fs.alloc_block(), Some(last));
+    }
+}
@@ -0,0 +1,393 @@
+use alloc::vec;
+use core::mem::size_of;
+
+use crate::device::BlockDevice;
+use crate::filesystem::FileSystem;
+use crate::*;
+
+// Get the n-th link in the indirect block
+pub(crate) fn indirect_link(buf: &[u8], n: usize) -> u32 {
+    let start = n * size_of::<u32>();
+    u32::from_le_bytes(buf[start..start + size_of::<u32>()].try_into().unwrap())
+}
+
+pub(crate) fn set_indirect_link(buf: &mut [u8], n: usize, block_no: u32) {
+    let start = n * size_of::<u32>();
+    buf[start..start + size_of::<u32>()].copy_from_slice(&block_no.to_le_bytes());
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    /* Get the block number for the n-th block of inode. Return None if
+     * the block is not allocated yet. */
+    pub fn bmap(&self, inode: &Inode, bn: usize) -> Option<u32> {
+        // For the first NDIRECT blocks, they are direct linked
+        let block_no = if bn < NDIRECT {
+            inode.directs[bn]
+        } else if bn < FILE_MAX_LINK {
+            if inode.indirect == 0 {
+                return None;
+            }
+
+            let mut buf = vec![0; BLKSZ];
+            self.device().read_block(inode.indirect, &mut buf);
+            indirect_link(&buf, bn - NDIRECT)
+        } else {
+            return None;
+        };
+
+        if block_no == 0 {
+            None
+        } else {
+            Some(block_no)
+        }
+    }
+
+    /* Count the blocks which are allocated to inode, including the
+     * indirect block. The hole in file doesn't take any block. */
+    pub fn nblocks(&self, inode: &Inode) -> usize {
+        let mut n = inode.directs.iter().filter(|&&b| b != 0).count();
+        if inode.indirect != 0 {
+            let mut buf = vec![0; BLKSZ];
+            self.device().read_block(inode.indirect, &mut buf);
+            n += 1
+                + (0..NINDIRECT)
+                    .filter(|&i| indirect_link(&buf, i) != 0)
+                    .count();
+        }
+
+        n
+    }
+
+    /* Get the block number for the n-th block of inode. If there is no
+     * corresponding block on this link, allocating one for it. Return
+     * None if the disk is full. */
+    pub fn bmap_alloc(&self, inode: &mut Inode, bn: usize) -> Option<u32> {
+        if let Some(block_no) = self.bmap(inode, bn) {
+            return Some(block_no);
+        }
+
+        if bn < NDIRECT {
+            let block_no = self.alloc_block()?;
+            inode.directs[bn] = block_no;
+            return Some(block_no);
+        }
+
+        if bn >= FILE_MAX_LINK {
+            return None;
+        }
+
+        if inode.indirect == 0 {
+            inode.indirect = self.alloc_block()?;
+        }
+
+        let mut buf = vec![0; BLKSZ];
+        self.device().read_block(inode.indirect, &mut buf);
+        let block_no = self.alloc_block()?;
+        set_indirect_link(&mut buf, bn - NDIRECT, block_no);
+        self.device().write_block(inode.indirect, &buf);
+
+        Some(block_no)
+    }
+
+    /* Change the size of inode. The blocks beyond the new size are
+     * released, and the tail of the last block is cleared, so it reads
+     * as zero if the file grows again. Growing the file leaves a hole,
+     * which takes no block until it is written. Return false if the size
+     * is beyond the maximum file size. */
+    pub fn truncate(&self, inode: &mut Inode, size: usize) -> bool {
+        if size > FILE_MAX_LINK * BLKSZ {
+            return false;
+        }
+
+        // The number of blocks to keep
+        let keep = (size + BLKSZ - 1) / BLKSZ;
+        for bn in keep.min(NDIRECT)..NDIRECT {
+            if inode.directs[bn] != 0 {
+                self.free_block(inode.directs[bn]);
+                inode.directs[bn] = 0;
+            }
+        }
+
+        if inode.indirect != 0 {
+            let mut buf = vec![0; BLKSZ];
+            self.device().read_block(inode.indirect, &mut buf);
+            for n in keep.saturating_sub(NDIRECT)..NINDIRECT {
+                let block_no = indirect_link(&buf, n);
+                if block_no != 0 {
+                    self.free_block(block_no);
+                    set_indirect_link(&mut buf, n, 0);
+                }
+            }
+
+            if keep <= NDIRECT {
+                self.free_block(inode.indirect);
+                inode.indirect = 0;
+            } else {
+                self.device().write_block(inode.indirect, &buf);
+            }
+        }
+
+        // The new blocks are zeroed, so only the shrunk one is cleared
+        if size < inode.size as usize && size % BLKSZ != 0 {
+            if let Some(block_no) = self.bmap(inode, size / BLKSZ) {
+                let mut buf = vec![0; BLKSZ];
+                self.device().read_block(block_no, &mut buf);
+                buf[size % BLKSZ..].fill(0);
+                self.device().write_block(block_no, &buf);
+            }
+        }
+
+        inode.size = size as u32;
+        inode.mtime = self.now();
+        inode.ctime = inode.mtime;
+        true
+    }
+
+    // Release all the data blocks of inode
+    pub fn itrunc(&self, inode: &mut Inode) {
+        self.truncate(inode, 0);
+    }
+
+    /* Read data from inode to the buffer, and return the number of bytes
+     * which are read. It stops at the end of file. */
+    pub fn readi(&self, inode: &Inode, mut off: usize, dst: &mut [u8]) -> usize {
+        let size = inode.size as usize;
+        if off >= size {
+            return 0;
+        }
+
+        let n = dst.len().min(size - off);
+        let mut total = 0;
+        let mut buf = vec![0; BLKSZ];
+
+        while total < n {
+            let len = (n - total).min(BLKSZ - off % BLKSZ);
+            match self.bmap(inode, off / BLKSZ) {
+                Some(block_no) => {
+                    self.device().read_block(block_no, &mut buf);
+                    let start = off % BLKSZ;
+                    dst[total..total + len].copy_from_slice(&buf[start..start + len]);
+                }
+                // The block which is never written is read as zero
+                None => dst[total..total + len].fill(0),
+            }
+
+            total += len;
+            off += len;
+        }
+
+        n
+    }
+
+    /* Write data from the buffer to inode, and return the number of bytes
+     * which are written. It may be less than the buffer if the disk is
+     * full. Writing beyond the end of file leaves a hole, and None is
+     * returned if it is beyond the maximum file size. The caller should
+     * write the inode back for the new size. */
+    pub fn writei(&self, inode: &mut Inode, mut off: usize, src: &[u8]) -> Option<usize> {
+        if off + src.len() > FILE_MAX_LINK * BLKSZ {
+            return None;
+        }
+
+        let mut total = 0;
+        let mut buf = vec![0; BLKSZ];
+
+        while total < src.len() {
+            let block_no = match self.bmap_alloc(inode, off / BLKSZ) {
+                Some(block_no) => block_no,
+                None => break,
+            };
+
+            let len = (src.len() - total).min(BLKSZ - off % BLKSZ);
+            let start = off % BLKSZ;
+            // Read, modify, and write back the block
+            self.device().read_block(block_no, &mut buf);
+            buf[start..start + len].copy_from_slice(&src[total..total + len]);
+            self.device().write_block(block_no, &buf);
+
+            total += len;
+            off += len;
+        }
+
+        if total > 0 {
+            inode.size = inode.size.max(off as u32);
+            inode.mtime = self.now();
+            inode.ctime = inode.mtime;
+        }
+
+        Some(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::MemDevice;
+    use alloc::vec::Vec;
+
+    fn new_file(fs: &FileSystem<MemDevice>) -> Inode {
+        let inum = fs.alloc_inode(T_FILE, 0, 0, 1).unwrap();
+        fs.read_inode(inum)
+    }
+
+    #[test]
+    fn write_then_read() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut inode = new_file(&fs);
+
+        let data = b"hello world";
+        assert_eq!(fs.writei(&mut inode, 0, data), Some(data.len()));
+        assert_eq!(inode.size as usize, data.len());
+
+        let mut buf = [0; 32];
+        assert_eq!(fs.readi(&inode, 0, &mut buf), data.len());
+        assert_eq!(&buf[..data.len()], data);
+
+        // Read from the middle and beyond the end of file
+        assert_eq!(fs.readi(&inode, 6, &mut buf), 5);
+        assert_eq!(&buf[..5], b"world");
+        assert_eq!(fs.readi(&inode, data.len(), &mut buf), 0);
+    }
+
+    #[test]
+    fn write_across_blocks() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut inode = new_file(&fs);
+
+        // Cover the direct blocks and some of the indirect blocks
+        let data: Vec<u8> = (0..(NDIRECT + 3) * BLKSZ + 10)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        assert_eq!(fs.writei(&mut inode, 0, &data), Some(data.len()));
+        assert!(inode.indirect != 0);
+
+        let mut buf = vec![0; data.len()];
+        assert_eq!(fs.readi(&inode, 0, &mut buf), data.len());
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn write_invalid_offset() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut inode = new_file(&fs);
+
+        // Can't write beyond the maximum file size
+        let data = vec![0; FILE_MAX_LINK * BLKSZ + 1];
+        assert_eq!(fs.writei(&mut inode, 0, &data), None);
+        assert_eq!(fs.writei(&mut inode, FILE_MAX_LINK * BLKSZ, b"x"), None);
+        assert_eq!(inode.size, 0);
+    }
+
+    #[test]
+    fn truncate_frees_blocks() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut inode = new_file(&fs);
+
+        let data = vec![1; (NDIRECT + 1) * BLKSZ];
+        fs.writei(&mut inode, 0, &data).unwrap();
+        let first = inode.directs[0];
+
+        fs.itrunc(&mut inode);
+        assert_eq!(inode.size, 0);
+        assert_eq!(inode.indirect, 0);
+        // The released blocks can be allocated again
+        assert_eq!(fs.alloc_block(), Some(first));
+    }
+
+    #[test]
+    fn update_timestamps() {
+        use core::sync::atomic::{AtomicU64, Ordering};
+        static NOW: AtomicU64 = AtomicU64::new(100);
+
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default())
+            .unwrap()
+            .with_clock(|| NOW.load(Ordering::Relaxed));
+        let mut inode = new_file(&fs);
+        assert_eq!((inode.atime, inode.mtime, inode.ctime), (100, 100, 100));
+        assert_eq!(inode.mode, 0o644);
+
+        // Only the modification changes the mtime and ctime
+        NOW.store(200, Ordering::Relaxed);
+        let mut buf = [0; 4];
+        fs.readi(&inode, 0, &mut buf);
+        assert_eq!(inode.mtime, 100);
+        fs.writei(&mut inode, 0, b"data");
+        assert_eq!((inode.atime, inode.mtime, inode.ctime), (100, 200, 200));
+
+        NOW.store(300, Ordering::Relaxed);
+        fs.itrunc(&mut inode);
+        assert_eq!((inode.atime, inode.mtime, inode.ctime), (100, 300, 300));
+    }
+
+    #[test]
+    fn check_permission() {
+        let mut inode = Inode::default();
+        inode.init(T_FILE, 0, 0, 1);
+        (inode.uid, inode.gid, inode.mode) = (1, 2, 0o640);
+
+        assert!(inode.permits(1, 0, MAY_READ | MAY_WRITE));
+        assert!(!inode.permits(1, 0, MAY_EXEC));
+        assert!(inode.permits(3, 2, MAY_READ));
+        assert!(!inode.permits(3, 2, MAY_WRITE));
+        assert!(!inode.permits(3, 3, MAY_READ));
+
+        // The superuser can execute only if anyone can
+        assert!(inode.permits(ROOT_UID, 0, MAY_READ | MAY_WRITE));
+        assert!(!inode.permits(ROOT_UID, 0, MAY_EXEC));
+        inode.mode |= 0o001;
+        assert!(inode.permits(ROOT_UID, 0, MAY_EXEC));
+        assert!(inode.permits(3, 3, MAY_EXEC));
+    }
+
+    #[test]
+    fn write_sparse_file() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut inode = new_file(&fs);
+
+        // The blocks before the data are left as hole
+        let off = (NDIRECT + 2) * BLKSZ + 10;
+        assert_eq!(fs.writei(&mut inode, off, b"end"), Some(3));
+        assert_eq!(inode.size as usize, off + 3);
+        assert_eq!(fs.bmap(&inode, 0), None);
+        assert_eq!(fs.bmap(&inode, NDIRECT), None);
+        assert!(fs.bmap(&inode, off / BLKSZ).is_some());
+
+        let mut buf = vec![1; off + 3];
+        assert_eq!(fs.readi(&inode, 0, &mut buf), off + 3);
+        assert!(buf[..off].iter().all(|&b| b == 0));
+        assert_eq!(&buf[off..], b"end");
+
+        // Only the written block and the indirect block are allocated
+        assert_eq!(fs.nblocks(&inode), 2);
+
+        // The empty write doesn't extend the file
+        assert_eq!(fs.writei(&mut inode, off + 100, b""), Some(0));
+        assert_eq!(inode.size as usize, off + 3);
+    }
+
+    #[test]
+    fn truncate_to_size() {
+        let fs = FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap();
+        let mut inode = new_file(&fs);
+        let data = vec![1; (NDIRECT + 2) * BLKSZ];
+        fs.writei(&mut inode, 0, &data).unwrap();
+
+        // Shrink into the direct blocks, and the indirect one is released
+        assert!(fs.truncate(&mut inode, 2 * BLKSZ + 5));
+        assert_eq!(inode.size as usize, 2 * BLKSZ + 5);
+        assert_eq!(inode.indirect, 0);
+        assert!(fs.bmap(&inode, 2).is_some());
+        assert_eq!(fs.bmap(&inode, 3), None);
+
+        // Grow again, and the truncated part reads as zero
+        assert!(fs.truncate(&mut inode, 4 * BLKSZ));
+        assert_eq!(fs.bmap(&inode, 3), None);
+        let mut buf = vec![1; 4 * BLKSZ];
+        assert_eq!(fs.readi(&inode, 0, &mut buf), 4 * BLKSZ);
+        assert!(buf[..2 * BLKSZ + 5].iter().all(|&b| b == 1));
+        assert!(buf[2 * BLKSZ + 5..].iter().all(|&b| b == 0));
+
+        assert!(!fs.truncate(&mut inode, FILE_MAX_LINK * BLKSZ + 1));
+        assert_eq!(inode.size as usize, 4 * BLKSZ);
+    }
+}
@@ -1,9 +1,28 @@
 /* The design of the filesystem can be referenced to
  * https://github.com/mit-pdos/xv6-riscv/blob/riscv/kernel/fs.h */
-#![no_std]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+extern crate alloc;
 
 use core::mem::size_of;
 
+#[cfg(any(test, feature = "std"))]
+mod check;
+mod device;
+mod dir;
+mod error;
+mod filesystem;
+mod inode;
+mod namespace;
+
+#[cfg(any(test, feature = "std"))]
+pub use check::Problem;
+pub use device::BlockDevice;
+#[cfg(feature = "std")]
+pub use device::FileDevice;
+pub use error::Error;
+pub use filesystem::{FileSystem, OpenError};
+
 // block size
 pub const BLKSZ: usize = 1024;
 // max number of blocks any FS op writes
@@ -11,24 +30,28 @@ pub const MAXOPBLOCKS: usize = 10;
 
 pub const INODES_PER_BLK: usize = BLKSZ / size_of::<Inode>();
 pub const BIT_PER_BLK: usize = BLKSZ * 8;
-pub const NINODES: u32 = 200;
 
-// size of file system in blocks
+/* The default geometry of file system. The actual one is chosen when the
+ * image is created, and recorded in the superblock. */
+// default number of inodes
+pub const NINODES: u32 = 200;
+// default size of file system in blocks
 pub const FS_BLKSZ: u32 = 2000;
-// size of log in blocks
+// default size of log in blocks
 pub const LOG_BLKSZ: u32 = MAXOPBLOCKS as u32 * 3;
-// size of inode in blocks
-pub const INODE_BLKSZ: u32 = NINODES.div_ceil(INODES_PER_BLK as u32);
-// size of bitmap in blocks
-pub const BITMAP_BLKSZ: u32 = FS_BLKSZ.div_ceil(BLKSZ as u32 * 8);
 
 // Inode number for root
 pub const ROOTINO: u32 = 1;
 //
 pub const MAGIC: u32 = 0x52696B6F;
+/* The revision of on-disk format, which is bumped for any incompatible
+ * change. The image without version field reads 0.
+ * - 1: Variable-length directory entries with 32-bit inode number
+ * - 2: 128-byte inodes with mode, owner and timestamps */
+pub const FS_VERSION: u32 = 2;
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct SuperBlock {
     pub magic: u32,
     // Size of file system image (blocks)
@@ -45,15 +68,109 @@ pub struct SuperBlock {
     pub inodestart: u32,
     // Block number of first free map block
     pub bmapstart: u32,
+    // Revision of the on-disk format
+    pub version: u32,
 }
 unsafe impl plain::Plain for SuperBlock {}
 
+impl SuperBlock {
+    /* Lay out the file system with the given geometry. Sequentially from
+     * the first block:
+     * - 1 block for boot block
+     * - 1 block for suberblock
+     * - nlog blocks for log block
+     * - inode_blksz() blocks for inode block
+     * - bitmap_blksz() blocks for bitmap block
+     * - the remaining blocks for data block
+     * Return None if the geometry can't make a usable file system. */
+    pub fn new(geo: &Geometry) -> Option<SuperBlock> {
+        if geo.ninodes == 0 {
+            return None;
+        }
+
+        let mut sb = SuperBlock {
+            magic: MAGIC,
+            fs_blksz: geo.fs_blksz,
+            nblocks: 0,
+            ninodes: geo.ninodes,
+            nlog: geo.nlog,
+            logstart: 2,
+            inodestart: 0,
+            bmapstart: 0,
+            version: FS_VERSION,
+        };
+        sb.inodestart = sb.logstart.checked_add(sb.nlog)?;
+        sb.bmapstart = sb.inodestart.checked_add(sb.inode_blksz())?;
+
+        // At least one data block is needed for the root directory
+        let nmeta = sb.bmapstart.checked_add(sb.bitmap_blksz())?;
+        if nmeta >= sb.fs_blksz {
+            return None;
+        }
+        sb.nblocks = sb.fs_blksz - nmeta;
+
+        Some(sb)
+    }
+
+    pub fn geometry(&self) -> Geometry {
+        Geometry {
+            fs_blksz: self.fs_blksz,
+            ninodes: self.ninodes,
+            nlog: self.nlog,
+        }
+    }
+
+    // Size of inode in blocks
+    pub fn inode_blksz(&self) -> u32 {
+        self.ninodes.div_ceil(INODES_PER_BLK as u32)
+    }
+
+    // Size of bitmap in blocks
+    pub fn bitmap_blksz(&self) -> u32 {
+        self.fs_blksz.div_ceil(BIT_PER_BLK as u32)
+    }
+
+    // Number of blocks before the first data block
+    pub fn nmeta(&self) -> u32 {
+        self.bmapstart + self.bitmap_blksz()
+    }
+}
+
+/* The geometry to create the file system with. Other parts of the
+ * layout are derived from these. */
+#[derive(Clone, Copy, Debug)]
+pub struct Geometry {
+    // Size of file system image (blocks)
+    pub fs_blksz: u32,
+    // Number of inodes
+    pub ninodes: u32,
+    // Number of log blocks
+    pub nlog: u32,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Geometry {
+            fs_blksz: FS_BLKSZ,
+            ninodes: NINODES,
+            nlog: LOG_BLKSZ,
+        }
+    }
+}
+
 // Directory type file
 pub const T_DIR: u16 = 1;
 // Normal File
 pub const T_FILE: u16 = 2;
 // Device type file
 pub const T_DEVICE: u16 = 3;
+// Symbolic link, whose data is the target path
+pub const T_SYMLINK: u16 = 4;
+
+// Maximum length of the target path of symbolic link
+pub const SYMLINK_MAX: usize = 4095;
+// Maximum number of symbolic links followed in a path
+pub const MAXSYMLINKS: usize = 40;
 
 // NDIRECT blocks in a file are described with direct link
 pub const NDIRECT: usize = 12;
@@ -63,8 +180,21 @@ pub const NINDIRECT: usize = BLKSZ / size_of::<u32>();
 /* A file's total blocks is not expected to go over the total availible links */
 pub const FILE_MAX_LINK: usize = NDIRECT + NINDIRECT;
 
+/* The permission bits in mode, which follow the ones in Unix with setuid,
+ * setgid and sticky bits. The type of file is not included, since it is
+ * kept in typ. */
+pub const S_IALLUGO: u32 = 0o7777;
+
+// The access to be checked by Inode::permits, which can be combined
+pub const MAY_EXEC: u32 = 0o1;
+pub const MAY_WRITE: u32 = 0o2;
+pub const MAY_READ: u32 = 0o4;
+
+// The uid of superuser, who is permitted to do almost everything
+pub const ROOT_UID: u32 = 0;
+
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Inode {
     // File type
     pub typ: u16,
@@ -76,10 +206,23 @@ pub struct Inode {
     pub nlink: u16,
     // Size of file (bytes)
     pub size: u32,
+    // Permission bits
+    pub mode: u32,
+    // Owner of file
+    pub uid: u32,
+    pub gid: u32,
+    /* Time of last access, modification and status change, in nanoseconds
+     * since epoch. The atime is not updated by readi, which is also used
+     * for lookup, so the caller updates it on reading for the user. */
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
     // Data block addresses for direct access
     pub directs: [u32; NDIRECT],
     // Data block addresses for indirect access
     pub indirect: u32,
+    // Pad the inode to 128 bytes for future use
+    pub reserved: [u32; 7],
 }
 unsafe impl plain::Plain for Inode {}
 impl Inode {
@@ -89,9 +232,8 @@ impl Inode {
             major,
             minor,
             nlink,
-            size: 0,
-            directs: [0; NDIRECT],
-            indirect: 0,
+            mode: default_mode(typ),
+            ..Default::default()
         }
     }
 
@@ -103,27 +245,121 @@ impl Inode {
     pub fn is_free(&self) -> bool {
         self.typ == 0
     }
+
+    /* Whether the user with uid and gid has the access of mask to the
+     * inode. The superuser can always read and write, and can execute
+     * if anyone can. */
+    pub fn permits(&self, uid: u32, gid: u32, mask: u32) -> bool {
+        if uid == ROOT_UID {
+            return mask & MAY_EXEC == 0 || self.typ == T_DIR || self.mode & 0o111 != 0;
+        }
+
+        let perm = if uid == self.uid {
+            self.mode >> 6
+        } else if gid == self.gid {
+            self.mode >> 3
+        } else {
+            self.mode
+        };
+        perm & mask & 0o7 == mask
+    }
 }
 
-pub const DIRSIZ: usize = 14;
+// The permission bits of a new inode, before it is changed by the owner
+pub fn default_mode(typ: u16) -> u32 {
+    match typ {
+        T_DIR => 0o755,
+        T_SYMLINK => 0o777,
+        _ => 0o644,
+    }
+}
+
+// Maximum length of file name
+pub const NAME_MAX: usize = 255;
+// The directory entries are aligned to this size
+pub const DIRENT_ALIGN: usize = 4;
+
+/* The directory entry on disk is a header followed by the name, and the
+ * record is padded for alignment. The entries never cross the block
+ * boundary, and the last entry of a block takes the remaining space. An
+ * entry with zero inum is unused. */
 #[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DirentHeader {
+    pub inum: u32,
+    // Length of the whole record, including the unused space after name
+    pub rec_len: u16,
+    pub name_len: u8,
+    pub reserved: u8,
+}
+unsafe impl plain::Plain for DirentHeader {}
+
+// The minimum length of record for the name
+pub const fn dirent_len(name_len: usize) -> usize {
+    (size_of::<DirentHeader>() + name_len).next_multiple_of(DIRENT_ALIGN)
+}
+
+// The directory entry read from disk
 #[derive(Clone, Copy, Debug)]
 pub struct Dirent {
-    pub inum: u16,
-    pub name: [u8; DIRSIZ],
+    pub inum: u32,
+    pub rec_len: u16,
+    name_len: u8,
+    name: [u8; NAME_MAX],
 }
 
 impl Dirent {
-    pub fn update(&mut self, inum: u32, s: &str) {
-        let slen = s.len();
-        // At least the last '\0' should be retained
-        assert!(slen < DIRSIZ - 1);
+    /* Create the entry with the minimum record length, return None if
+     * the name is too long. */
+    pub fn new(inum: u32, name: &str) -> Option<Self> {
+        let name_len = name.len();
+        if name_len > NAME_MAX {
+            return None;
+        }
+
+        let mut dirent = Dirent {
+            inum,
+            rec_len: dirent_len(name_len) as u16,
+            name_len: name_len as u8,
+            name: [0; NAME_MAX],
+        };
+        dirent.name[..name_len].copy_from_slice(name.as_bytes());
+        Some(dirent)
+    }
+
+    pub fn from_header(header: &DirentHeader, name: &[u8]) -> Self {
+        let mut dirent = Dirent {
+            inum: header.inum,
+            rec_len: header.rec_len,
+            name_len: header.name_len,
+            name: [0; NAME_MAX],
+        };
+        dirent.name[..name.len()].copy_from_slice(name);
+        dirent
+    }
+
+    pub fn header(&self) -> DirentHeader {
+        DirentHeader {
+            inum: self.inum,
+            rec_len: self.rec_len,
+            name_len: self.name_len,
+            reserved: 0,
+        }
+    }
 
-        // TODO: take care of truncation after casting
-        self.inum = inum as u16;
-        self.name[0..slen].copy_from_slice(s.as_bytes());
-        self.name[slen] = 0;
+    pub fn name(&self) -> &[u8] {
+        &self.name[..self.name_len as usize]
     }
+
+    // The length which is really used by this entry
+    pub fn used_len(&self) -> usize {
+        dirent_len(self.name_len as usize)
+    }
+}
+
+// Whether any element of the path is longer than NAME_MAX
+pub fn name_too_long(path: &str) -> bool {
+    path.split('/').any(|name| name.len() > NAME_MAX)
 }
 
 // Block containing inode i
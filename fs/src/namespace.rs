@@ -0,0 +1,601 @@
+/* The operations on the namespace of filesystem. The relative paths are
+ * resolved from the directory `cwd`.
+ *
+ * There's no journal yet, so the operations are ordered to keep the
+ * filesystem recoverable by fsck if they are interrupted: a new link is
+ * always counted and added before the old one is removed. */
+use alloc::string::String;
+
+use crate::device::BlockDevice;
+use crate::error::Error;
+use crate::filesystem::FileSystem;
+use crate::*;
+
+fn is_dot(name: &str) -> bool {
+    name == "." || name == ".."
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    /* Find the parent directory by the path with the last element. The
+     * path like "/" has no parent, which is busy for the operations. The
+     * removed directory which is still opened can't have new entries, so
+     * it is not found as a parent. */
+    fn find_parent<'a>(&self, cwd: u32, path: &'a str) -> Result<(u32, &'a str), Error> {
+        if name_too_long(path) {
+            return Err(Error::NameTooLong);
+        }
+
+        match self.lookup_parent(cwd, path) {
+            Err(Error::NotFound) if self.namei(cwd, path).is_some() => Err(Error::Busy),
+            Ok((dir, _)) if self.read_inode(dir).nlink == 0 => Err(Error::NotFound),
+            result => result,
+        }
+    }
+
+    fn adjust_nlink(&self, inum: u32, delta: i32) {
+        let mut inode = self.read_inode(inum);
+        inode.nlink = (inode.nlink as i32 + delta) as u16;
+        inode.ctime = self.now();
+        self.write_inode(inum, &inode);
+    }
+
+    /* Drop a link to the inode, and release it if there's no link left.
+     * The inode which is still opened is kept as an orphan. */
+    fn drop_link(&self, inum: u32) {
+        let mut inode = self.read_inode(inum);
+        inode.nlink = inode.nlink.saturating_sub(1);
+        if inode.nlink == 0 && !self.is_open(inum) {
+            self.free_inode(inum, &mut inode);
+        } else {
+            inode.ctime = self.now();
+            self.write_inode(inum, &inode);
+        }
+    }
+
+    // Whether the directory `inum` is `ancestor` or under it
+    fn is_ancestor(&self, ancestor: u32, mut inum: u32) -> bool {
+        // The depth can't exceed the number of inodes without a loop
+        for _ in 0..self.sb().ninodes {
+            if inum == ancestor {
+                return true;
+            }
+            if inum == ROOTINO {
+                return false;
+            }
+
+            match self.dirlookup(&self.read_inode(inum), "..") {
+                Some(parent) => inum = parent,
+                None => return false,
+            }
+        }
+
+        false
+    }
+
+    pub fn mkdir(&self, cwd: u32, path: &str) -> Result<u32, Error> {
+        let (dir, name) = match self.find_parent(cwd, path) {
+            Err(Error::Busy) => return Err(Error::Exists),
+            result => result?,
+        };
+
+        let parent = self.read_inode(dir);
+        if is_dot(name) || self.dirlookup(&parent, name).is_some() {
+            return Err(Error::Exists);
+        }
+        // The ".." of the new directory is counted by parent
+        if parent.nlink == u16::MAX {
+            return Err(Error::TooManyLinks);
+        }
+
+        self.create(dir, name, T_DIR, 0, 0).ok_or(Error::NoSpace)
+    }
+
+    /* Create a regular file or device at the path, and return its inode
+     * number. The directory and symbolic link have their own operations. */
+    pub fn mknod(
+        &self,
+        cwd: u32,
+        path: &str,
+        typ: u16,
+        major: u16,
+        minor: u16,
+    ) -> Result<u32, Error> {
+        if typ != T_FILE && typ != T_DEVICE {
+            return Err(Error::Invalid);
+        }
+
+        let (dir, name) = match self.find_parent(cwd, path) {
+            Err(Error::Busy) => return Err(Error::Exists),
+            result => result?,
+        };
+        if is_dot(name) || self.dirlookup(&self.read_inode(dir), name).is_some() {
+            return Err(Error::Exists);
+        }
+
+        self.create(dir, name, typ, major, minor)
+            .ok_or(Error::NoSpace)
+    }
+
+    // Remove the name of file, and release it if it is the last link
+    pub fn unlink(&self, cwd: u32, path: &str) -> Result<(), Error> {
+        let (dir, name) = match self.find_parent(cwd, path) {
+            Err(Error::Busy) => return Err(Error::IsDir),
+            result => result?,
+        };
+        if is_dot(name) {
+            return Err(Error::IsDir);
+        }
+
+        let mut parent = self.read_inode(dir);
+        let inum = self.dirlookup(&parent, name).ok_or(Error::NotFound)?;
+        if self.read_inode(inum).typ == T_DIR {
+            return Err(Error::IsDir);
+        }
+
+        self.dirunlink(&mut parent, name).ok_or(Error::NotFound)?;
+        self.write_inode(dir, &parent);
+        self.drop_link(inum);
+
+        Ok(())
+    }
+
+    pub fn rmdir(&self, cwd: u32, path: &str) -> Result<(), Error> {
+        let (dir, name) = self.find_parent(cwd, path)?;
+        match name {
+            "." => return Err(Error::Invalid),
+            ".." => return Err(Error::NotEmpty),
+            _ => {}
+        }
+
+        let mut parent = self.read_inode(dir);
+        let inum = self.dirlookup(&parent, name).ok_or(Error::NotFound)?;
+        let inode = self.read_inode(inum);
+        if inode.typ != T_DIR {
+            return Err(Error::NotDir);
+        }
+        if !self.dir_is_empty(&inode) {
+            return Err(Error::NotEmpty);
+        }
+
+        self.dirunlink(&mut parent, name).ok_or(Error::NotFound)?;
+        // The ".." of the removed directory is gone
+        parent.nlink -= 1;
+        self.write_inode(dir, &parent);
+        self.drop_link(inum);
+
+        Ok(())
+    }
+
+    // Make a new name for the file
+    pub fn link(&self, cwd: u32, old: &str, new: &str) -> Result<(), Error> {
+        if name_too_long(old) {
+            return Err(Error::NameTooLong);
+        }
+        // The link is made to the symbolic link itself
+        let inum = self.lookup(cwd, old, false)?;
+        let inode = self.read_inode(inum);
+        if inode.typ == T_DIR {
+            return Err(Error::NotPermitted);
+        }
+
+        let (dir, name) = match self.find_parent(cwd, new) {
+            Err(Error::Busy) => return Err(Error::Exists),
+            result => result?,
+        };
+        let mut parent = self.read_inode(dir);
+        if is_dot(name) || self.dirlookup(&parent, name).is_some() {
+            return Err(Error::Exists);
+        }
+        if inode.nlink == u16::MAX {
+            return Err(Error::TooManyLinks);
+        }
+
+        self.adjust_nlink(inum, 1);
+        if !self.dirlink(&mut parent, name, inum) {
+            self.adjust_nlink(inum, -1);
+            return Err(Error::NoSpace);
+        }
+        self.write_inode(dir, &parent);
+
+        Ok(())
+    }
+
+    // Make a symbolic link at the path, which refers to the target
+    pub fn symlink(&self, cwd: u32, target: &str, path: &str) -> Result<u32, Error> {
+        if target.is_empty() {
+            return Err(Error::NotFound);
+        }
+        if target.len() > SYMLINK_MAX {
+            return Err(Error::NameTooLong);
+        }
+
+        let (dir, name) = match self.find_parent(cwd, path) {
+            Err(Error::Busy) => return Err(Error::Exists),
+            result => result?,
+        };
+        if is_dot(name) || self.dirlookup(&self.read_inode(dir), name).is_some() {
+            return Err(Error::Exists);
+        }
+
+        let inum = self
+            .create(dir, name, T_SYMLINK, 0, 0)
+            .ok_or(Error::NoSpace)?;
+        let mut inode = self.read_inode(inum);
+        if self.writei(&mut inode, 0, target.as_bytes()) != Some(target.len()) {
+            self.unlink(dir, name)?;
+            return Err(Error::NoSpace);
+        }
+        self.write_inode(inum, &inode);
+
+        Ok(inum)
+    }
+
+    // Read the target of the symbolic link at the path
+    pub fn readlink(&self, cwd: u32, path: &str) -> Result<String, Error> {
+        if name_too_long(path) {
+            return Err(Error::NameTooLong);
+        }
+
+        let inode = self.read_inode(self.lookup(cwd, path, false)?);
+        self.read_symlink(&inode).ok_or(Error::Invalid)
+    }
+
+    /* Move the file to the new path. If the new path exists, it is
+     * replaced in place, so it always refers to either the old or the
+     * new file. A directory can only replace an empty directory. */
+    pub fn rename(&self, cwd: u32, old: &str, new: &str) -> Result<(), Error> {
+        let (old_dir, old_name) = self.find_parent(cwd, old)?;
+        let (new_dir, new_name) = self.find_parent(cwd, new)?;
+        if is_dot(old_name) || is_dot(new_name) {
+            return Err(Error::Invalid);
+        }
+
+        let src = self
+            .dirlookup(&self.read_inode(old_dir), old_name)
+            .ok_or(Error::NotFound)?;
+        let is_dir = self.read_inode(src).typ == T_DIR;
+        // The directory can't be moved under itself
+        if is_dir && self.is_ancestor(src, new_dir) {
+            return Err(Error::Invalid);
+        }
+
+        let dst = self.dirlookup(&self.read_inode(new_dir), new_name);
+        match dst {
+            // They are the links to the same file
+            Some(dst) if dst == src => return Ok(()),
+            Some(dst) => {
+                let inode = self.read_inode(dst);
+                if is_dir && inode.typ != T_DIR {
+                    return Err(Error::NotDir);
+                }
+                if !is_dir && inode.typ == T_DIR {
+                    return Err(Error::IsDir);
+                }
+                if is_dir && !self.dir_is_empty(&inode) {
+                    return Err(Error::NotEmpty);
+                }
+            }
+            None => {
+                if is_dir && new_dir != old_dir && self.read_inode(new_dir).nlink == u16::MAX {
+                    return Err(Error::TooManyLinks);
+                }
+            }
+        }
+
+        // Link the file to the new path first
+        let mut parent = self.read_inode(new_dir);
+        match dst {
+            Some(dst) => {
+                self.dirreplace(&mut parent, new_name, src)
+                    .ok_or(Error::NotFound)?;
+                self.write_inode(new_dir, &parent);
+
+                self.drop_link(dst);
+                if is_dir {
+                    self.adjust_nlink(new_dir, -1);
+                }
+            }
+            None => {
+                if !self.dirlink(&mut parent, new_name, src) {
+                    return Err(Error::NoSpace);
+                }
+                self.write_inode(new_dir, &parent);
+            }
+        }
+
+        // Then remove it from the old one
+        let mut parent = self.read_inode(old_dir);
+        self.dirunlink(&mut parent, old_name);
+        self.write_inode(old_dir, &parent);
+
+        // The ".." of the directory is moved to the new parent
+        if is_dir && old_dir != new_dir {
+            let mut inode = self.read_inode(src);
+            self.dirreplace(&mut inode, "..", new_dir);
+            self.write_inode(src, &inode);
+            self.adjust_nlink(old_dir, -1);
+            self.adjust_nlink(new_dir, 1);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::MemDevice;
+
+    fn new_fs() -> FileSystem<MemDevice> {
+        FileSystem::format(MemDevice::new(FS_BLKSZ), &Geometry::default()).unwrap()
+    }
+
+    fn new_file(fs: &FileSystem<MemDevice>, path: &str) -> u32 {
+        let (dir, name) = fs.nameiparent(ROOTINO, path).unwrap();
+        fs.create(dir, name, T_FILE, 0, 0).unwrap()
+    }
+
+    fn nlink(fs: &FileSystem<MemDevice>, inum: u32) -> u16 {
+        fs.read_inode(inum).nlink
+    }
+
+    #[test]
+    fn make_and_remove_directory() {
+        let fs = new_fs();
+        let dir = fs.mkdir(ROOTINO, "/dir").unwrap();
+        let sub = fs.mkdir(dir, "sub/").unwrap();
+        assert_eq!(fs.namei(ROOTINO, "/dir/sub"), Some(sub));
+        assert_eq!(nlink(&fs, dir), 2);
+
+        assert_eq!(fs.mkdir(ROOTINO, "/dir"), Err(Error::Exists));
+        assert_eq!(fs.mkdir(ROOTINO, "/"), Err(Error::Exists));
+        assert_eq!(fs.mkdir(ROOTINO, "/missing/x"), Err(Error::NotFound));
+        let long_name = "x".repeat(NAME_MAX + 1);
+        assert_eq!(fs.mkdir(ROOTINO, &long_name), Err(Error::NameTooLong));
+
+        assert_eq!(fs.rmdir(ROOTINO, "/dir"), Err(Error::NotEmpty));
+        assert_eq!(fs.rmdir(ROOTINO, "/"), Err(Error::Busy));
+        assert_eq!(fs.rmdir(dir, "."), Err(Error::Invalid));
+        assert_eq!(fs.rmdir(sub, ".."), Err(Error::NotEmpty));
+        new_file(&fs, "/file");
+        assert_eq!(fs.rmdir(ROOTINO, "/file"), Err(Error::NotDir));
+
+        assert_eq!(fs.rmdir(ROOTINO, "/dir/sub"), Ok(()));
+        assert_eq!(nlink(&fs, dir), 1);
+        assert_eq!(fs.rmdir(ROOTINO, "/dir"), Ok(()));
+        assert_eq!(fs.namei(ROOTINO, "/dir"), None);
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn make_node() {
+        let fs = new_fs();
+        let dev = fs.mknod(ROOTINO, "/dev", T_DEVICE, 1, 2).unwrap();
+        let inode = fs.read_inode(dev);
+        assert_eq!((inode.typ, inode.major, inode.minor), (T_DEVICE, 1, 2));
+        assert_eq!(fs.namei(ROOTINO, "/dev"), Some(dev));
+
+        assert_eq!(fs.mknod(ROOTINO, "/dev", T_FILE, 0, 0), Err(Error::Exists));
+        assert_eq!(fs.mknod(ROOTINO, "/", T_FILE, 0, 0), Err(Error::Exists));
+        assert_eq!(
+            fs.mknod(ROOTINO, "/a/b", T_FILE, 0, 0),
+            Err(Error::NotFound)
+        );
+        assert_eq!(
+            fs.mknod(ROOTINO, "/dev/a", T_FILE, 0, 0),
+            Err(Error::NotDir)
+        );
+        assert_eq!(fs.mknod(ROOTINO, "/dir", T_DIR, 0, 0), Err(Error::Invalid));
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn link_and_unlink_file() {
+        let fs = new_fs();
+        let dir = fs.mkdir(ROOTINO, "/dir").unwrap();
+        let file = new_file(&fs, "/file");
+
+        assert_eq!(fs.link(ROOTINO, "/file", "/dir/other"), Ok(()));
+        assert_eq!(fs.namei(ROOTINO, "/dir/other"), Some(file));
+        assert_eq!(nlink(&fs, file), 2);
+
+        assert_eq!(fs.link(ROOTINO, "/file", "/dir/other"), Err(Error::Exists));
+        assert_eq!(fs.link(ROOTINO, "/dir", "/x"), Err(Error::NotPermitted));
+        assert_eq!(fs.link(ROOTINO, "/missing", "/x"), Err(Error::NotFound));
+        assert_eq!(fs.unlink(ROOTINO, "/dir"), Err(Error::IsDir));
+        assert_eq!(fs.unlink(ROOTINO, "/missing"), Err(Error::NotFound));
+
+        // The file is released with the last link
+        assert_eq!(fs.unlink(ROOTINO, "/file"), Ok(()));
+        assert_eq!(nlink(&fs, file), 1);
+        assert_eq!(fs.unlink(dir, "other"), Ok(()));
+        assert!(fs.read_inode(file).is_free());
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn keep_open_orphan() {
+        let fs = new_fs().with_open_check(|_| true);
+        let file = new_file(&fs, "/file");
+        let mut inode = fs.read_inode(file);
+        fs.writei(&mut inode, 0, b"data").unwrap();
+        fs.write_inode(file, &inode);
+
+        // The open file is kept with its data after the last link is gone
+        assert_eq!(fs.unlink(ROOTINO, "/file"), Ok(()));
+        assert_eq!(fs.namei(ROOTINO, "/file"), None);
+        let inode = fs.read_inode(file);
+        assert!(!inode.is_free());
+        assert_eq!(inode.nlink, 0);
+        let mut buf = [0; 4];
+        assert_eq!(fs.readi(&inode, 0, &mut buf), 4);
+        assert_eq!(&buf, b"data");
+
+        assert!(fs.release_orphan(file));
+        assert!(fs.read_inode(file).is_free());
+        assert!(!fs.release_orphan(ROOTINO));
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn keep_open_directory() {
+        let fs = new_fs().with_open_check(|_| true);
+        let dir = fs.mkdir(ROOTINO, "/dir").unwrap();
+        let dst = fs.mkdir(ROOTINO, "/dst").unwrap();
+
+        // The removed directory can't have new entries while it is kept
+        assert_eq!(fs.rmdir(ROOTINO, "/dir"), Ok(()));
+        let inode = fs.read_inode(dir);
+        assert!(!inode.is_free());
+        assert_eq!((inode.typ, inode.nlink), (T_DIR, 0));
+        assert_eq!(fs.mkdir(dir, "sub"), Err(Error::NotFound));
+        assert_eq!(nlink(&fs, ROOTINO), 2);
+
+        // So is the directory replaced by rename
+        assert_eq!(fs.mkdir(ROOTINO, "/src").map(|_| ()), Ok(()));
+        assert_eq!(fs.rename(ROOTINO, "/src", "/dst"), Ok(()));
+        assert!(!fs.read_inode(dst).is_free());
+        assert_eq!(nlink(&fs, dst), 0);
+        assert_eq!(nlink(&fs, ROOTINO), 2);
+
+        assert!(fs.release_orphan(dir));
+        assert!(fs.release_orphan(dst));
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn rename_file() {
+        let fs = new_fs();
+        let dir = fs.mkdir(ROOTINO, "/dir").unwrap();
+        let a = new_file(&fs, "/a");
+        let b = new_file(&fs, "/dir/b");
+
+        // Move to another directory
+        assert_eq!(fs.rename(ROOTINO, "/a", "/dir/a"), Ok(()));
+        assert_eq!(fs.namei(ROOTINO, "/a"), None);
+        assert_eq!(fs.namei(ROOTINO, "/dir/a"), Some(a));
+
+        // Replace the existing one, which is released
+        assert_eq!(fs.rename(dir, "a", "b"), Ok(()));
+        assert_eq!(fs.namei(dir, "a"), None);
+        assert_eq!(fs.namei(dir, "b"), Some(a));
+        assert!(fs.read_inode(b).is_free());
+
+        assert_eq!(fs.rename(dir, "b", "b"), Ok(()));
+        assert_eq!(fs.rename(dir, "b", "/dir"), Err(Error::IsDir));
+        assert_eq!(fs.rename(dir, "missing", "c"), Err(Error::NotFound));
+        assert_eq!(fs.rename(dir, "b", "."), Err(Error::Invalid));
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn rename_directory() {
+        let fs = new_fs();
+        let a = fs.mkdir(ROOTINO, "/a").unwrap();
+        let b = fs.mkdir(ROOTINO, "/b").unwrap();
+        let sub = fs.mkdir(a, "sub").unwrap();
+        new_file(&fs, "/a/sub/file");
+
+        // The ".." and the link counts of parents are updated
+        assert_eq!(fs.rename(ROOTINO, "/a/sub", "/b/sub"), Ok(()));
+        assert_eq!(fs.namei(sub, ".."), Some(b));
+        assert_eq!((nlink(&fs, a), nlink(&fs, b)), (1, 2));
+        assert!(fs.namei(ROOTINO, "/b/sub/file").is_some());
+
+        // Can't be moved under itself
+        assert_eq!(fs.rename(ROOTINO, "/b", "/b/sub/b"), Err(Error::Invalid));
+        assert_eq!(fs.rename(ROOTINO, "/b", "/b/x"), Err(Error::Invalid));
+
+        // Only the empty directory can be replaced
+        assert_eq!(fs.rename(ROOTINO, "/a", "/b"), Err(Error::NotEmpty));
+        assert_eq!(fs.rename(ROOTINO, "/b", "/b/sub/file"), Err(Error::Invalid));
+        assert_eq!(
+            fs.rename(ROOTINO, "/b/sub", "/b/sub/file/"),
+            Err(Error::Invalid)
+        );
+        new_file(&fs, "/file");
+        assert_eq!(fs.rename(ROOTINO, "/a", "/file"), Err(Error::NotDir));
+        assert_eq!(fs.rename(ROOTINO, "/b/sub", "/a"), Ok(()));
+        assert_eq!(fs.namei(ROOTINO, "/a"), Some(sub));
+        assert_eq!(fs.namei(sub, ".."), Some(ROOTINO));
+        assert_eq!(nlink(&fs, b), 1);
+
+        assert_eq!(fs.rename(ROOTINO, "/", "/x"), Err(Error::Busy));
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn follow_symlinks() {
+        let fs = new_fs();
+        let dir = fs.mkdir(ROOTINO, "/dir").unwrap();
+        let file = new_file(&fs, "/dir/file");
+        let abs = fs.symlink(ROOTINO, "/dir", "/abs").unwrap();
+        let rel = fs.symlink(ROOTINO, "file", "/dir/rel").unwrap();
+        fs.symlink(ROOTINO, "../abs/rel", "/dir/chain").unwrap();
+
+        // The links in the middle are always followed
+        assert_eq!(fs.namei(ROOTINO, "/abs/file"), Some(file));
+        assert_eq!(fs.lookup(ROOTINO, "/abs/file", false), Ok(file));
+        // The relative target is started from the directory of link
+        assert_eq!(fs.namei(ROOTINO, "/dir/rel"), Some(file));
+        assert_eq!(fs.namei(dir, "chain"), Some(file));
+        assert_eq!(fs.lookup(ROOTINO, "/dir/rel", false), Ok(rel));
+        assert_eq!(fs.lookup(ROOTINO, "/abs", false), Ok(abs));
+        assert_eq!(fs.lookup(ROOTINO, "/abs/", false), Ok(dir));
+        assert_eq!(fs.lookup(ROOTINO, "/dir/rel/", true), Err(Error::NotDir));
+        assert_eq!(fs.lookup_parent(ROOTINO, "/abs/rel"), Ok((dir, "rel")));
+
+        assert_eq!(fs.readlink(ROOTINO, "/abs").as_deref(), Ok("/dir"));
+        assert_eq!(fs.readlink(ROOTINO, "/abs/"), Err(Error::Invalid));
+        assert_eq!(fs.readlink(ROOTINO, "/dir/file"), Err(Error::Invalid));
+        assert_eq!(fs.symlink(ROOTINO, "x", "/abs"), Err(Error::Exists));
+        assert_eq!(fs.symlink(ROOTINO, "", "/empty"), Err(Error::NotFound));
+
+        // The dangling link can be made, but not followed
+        fs.symlink(ROOTINO, "/missing", "/dangling").unwrap();
+        assert_eq!(fs.lookup(ROOTINO, "/dangling", true), Err(Error::NotFound));
+        assert!(fs.lookup(ROOTINO, "/dangling", false).is_ok());
+
+        // The link itself is linked, unlinked or renamed
+        assert_eq!(fs.link(ROOTINO, "/abs", "/abs2"), Ok(()));
+        assert_eq!(fs.lookup(ROOTINO, "/abs2", false), Ok(abs));
+        assert_eq!(fs.unlink(ROOTINO, "/abs"), Ok(()));
+        assert_eq!(fs.rename(ROOTINO, "/abs2", "/abs"), Ok(()));
+        assert_eq!(fs.rmdir(ROOTINO, "/abs"), Err(Error::NotDir));
+        assert_eq!(fs.namei(ROOTINO, "/dir/file"), Some(file));
+        assert_eq!(fs.check(false), []);
+    }
+
+    #[test]
+    fn symlink_loop() {
+        let fs = new_fs();
+        fs.symlink(ROOTINO, "b", "/a").unwrap();
+        fs.symlink(ROOTINO, "a", "/b").unwrap();
+        fs.symlink(ROOTINO, "self/x", "/self").unwrap();
+        assert_eq!(fs.lookup(ROOTINO, "/a", true), Err(Error::Loop));
+        assert_eq!(fs.lookup(ROOTINO, "/self", true), Err(Error::Loop));
+        assert_eq!(fs.lookup(ROOTINO, "/a/x", false), Err(Error::Loop));
+
+        // A long chain is followed under the limit
+        let file = new_file(&fs, "/file");
+        let mut target = String::from("file");
+        for i in 0..MAXSYMLINKS {
+            let name = format!("l{}", i);
+            fs.symlink(ROOTINO, &target, &name).unwrap();
+            target = name;
+        }
+        assert_eq!(fs.namei(ROOTINO, &target), Some(file));
+        fs.symlink(ROOTINO, &target, "/over").unwrap();
+        assert_eq!(fs.lookup(ROOTINO, "/over", true), Err(Error::Loop));
+
+        let long_target = "x".repeat(SYMLINK_MAX + 1);
+        assert_eq!(
+            fs.symlink(ROOTINO, &long_target, "/long"),
+            Err(Error::NameTooLong)
+        );
+        let long_target = "x/".repeat(SYMLINK_MAX / 2);
+        let inum = fs.symlink(ROOTINO, &long_target, "/long").unwrap();
+        assert_eq!(fs.readlink(ROOTINO, "/long"), Ok(long_target));
+        assert!(fs.read_inode(inum).size as usize > BLKSZ);
+    }
+}
@@ -0,0 +1,86 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::process::exit;
+
+use fs::*;
+
+/* The exit code follows e2fsck, which can be combined together:
+ * 0 for no error, 1 for errors corrected, 4 for errors left uncorrected,
+ * and 8 for operational error. */
+const EXIT_OK: i32 = 0;
+const EXIT_CORRECTED: i32 = 1;
+const EXIT_UNCORRECTED: i32 = 4;
+const EXIT_ERROR: i32 = 8;
+
+fn usage(prog: &str) -> ! {
+    println!("Usage: {} [-y|--repair] <image>", prog);
+    println!("Check the consistency of image, and repair it with -y.");
+    exit(EXIT_ERROR);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let (repair, img_name) = match &args[1..] {
+        [img] if !img.starts_with('-') => (false, img),
+        [opt, img] if opt == "-y" || opt == "--repair" => (true, img),
+        _ => usage(&args[0]),
+    };
+
+    let file = match OpenOptions::new().read(true).write(repair).open(img_name) {
+        Ok(file) => file,
+        Err(e) => {
+            println!("open {}: {}", img_name, e);
+            exit(EXIT_ERROR);
+        }
+    };
+    let len = file.metadata().expect("metadata()").len();
+
+    // The image should be large enough for the superblock at least
+    if len < 2 * BLKSZ as u64 {
+        println!("{}: too small to be an image", img_name);
+        exit(EXIT_ERROR);
+    }
+
+    let fs = match FileSystem::open(FileDevice::new(file)) {
+        Ok(fs) => fs,
+        Err(e) => {
+            println!("{}: {}", img_name, e);
+            exit(EXIT_UNCORRECTED);
+        }
+    };
+
+    let sb = fs.sb();
+    if len < sb.fs_blksz as u64 * BLKSZ as u64 {
+        println!(
+            "{}: the image is truncated, {} blocks are expected",
+            img_name, sb.fs_blksz
+        );
+        exit(EXIT_UNCORRECTED);
+    }
+
+    let problems = fs.check(repair);
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    let uncorrected = problems
+        .iter()
+        .filter(|p| !repair || !p.repairable())
+        .count();
+    println!(
+        "{}: {} problems found, {} corrected",
+        img_name,
+        problems.len(),
+        problems.len() - uncorrected
+    );
+
+    let mut code = EXIT_OK;
+    if uncorrected < problems.len() {
+        code |= EXIT_CORRECTED;
+    }
+    if uncorrected > 0 {
+        code |= EXIT_UNCORRECTED;
+    }
+    exit(code);
+}
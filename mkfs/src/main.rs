@@ -1,214 +1,194 @@
-use std::cell::{Cell, RefCell};
 use std::env;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
-use std::mem::{size_of, MaybeUninit};
-use std::slice;
+use std::fs::{self as host_fs, Metadata, OpenOptions};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::exit;
 
 use fs::*;
 
-thread_local! {
-    static FSFILE: RefCell<File> =
-                    RefCell::new(unsafe { MaybeUninit::zeroed().assume_init() });
-    static ALLOC_BLOCK: Cell<u32> =
-                    Cell::new(unsafe { MaybeUninit::zeroed().assume_init() });
-    // NOTE: This is a 1-based value
-    static NEXT_INUM: Cell<u32> = Cell::new(1);
-}
-
-fn as_slice<T: Sized>(p: &T) -> &[u8] {
-    unsafe { slice::from_raw_parts((p as *const T) as *const u8, size_of::<T>()) }
-}
+// Copy the content of host file to the inode
+fn copy_file(fs: &FileSystem<FileDevice>, inum: u32, path: &Path) {
+    let data = host_fs::read(path).unwrap_or_else(|e| panic!("read {:?}: {}", path, e));
 
-fn wsect(sec: u32, buf: &[u8]) {
-    let off = FSFILE.with(|f| {
-        f.borrow_mut()
-            .seek(SeekFrom::Start(sec as u64 * BLKSZ as u64))
-            .expect("seek for wsect fail")
-    });
-    assert!(off == sec as u64 * BLKSZ as u64);
+    let mut inode = fs.read_inode(inum);
+    let n = fs.writei(&mut inode, 0, &data);
+    fs.write_inode(inum, &inode);
 
-    let size = FSFILE.with(|f| f.borrow_mut().write(buf).expect("write for wsect fail"));
-    assert!(size == BLKSZ);
+    if n != Some(data.len()) {
+        panic!("{:?} is too large or the image is full", path);
+    }
 }
 
-fn rsect(sec: u32, buf: &mut [u8]) {
-    let off = FSFILE.with(|f| {
-        f.borrow_mut()
-            .seek(SeekFrom::Start(sec as u64 * BLKSZ as u64))
-            .expect("seek for rsect fail")
-    });
-    assert!(off == sec as u64 * BLKSZ as u64);
-
-    let size = FSFILE.with(|f| f.borrow_mut().read(buf).expect("read for rsect fail"));
-    assert!(size == BLKSZ);
+/* Copy the permission bits and modification time of host file to the
+ * inode. The owner is not copied, so everything in image is owned by
+ * root, as the kernel has no other user. */
+fn copy_attr(fs: &FileSystem<FileDevice>, inum: u32, meta: &Metadata) {
+    let mut inode = fs.read_inode(inum);
+    inode.mode = meta.mode() & S_IALLUGO;
+    let mtime = meta.mtime() as u64 * 1_000_000_000 + meta.mtime_nsec() as u64;
+    (inode.atime, inode.mtime, inode.ctime) = (mtime, mtime, mtime);
+    fs.write_inode(inum, &inode);
 }
 
-fn rinode(sb: &SuperBlock, inum: u32) -> Inode {
-    let mut buf = [0; BLKSZ];
-
-    let block = iblock(sb, inum);
-    rsect(block, &mut buf);
-
-    assert!(inum > 0);
-
-    let inode_ptr = block_inode(&mut buf, inum);
-    *inode_ptr
-}
+/* Add the host file or directory under the directory of image with the
+ * given name. The directory is added recursively. */
+fn add_path(fs: &FileSystem<FileDevice>, dir: u32, name: &str, path: &Path) {
+    let meta = host_fs::symlink_metadata(path).unwrap_or_else(|e| panic!("stat {:?}: {}", path, e));
+    let typ = if meta.is_dir() {
+        T_DIR
+    } else if meta.is_file() {
+        T_FILE
+    } else if meta.is_symlink() {
+        T_SYMLINK
+    } else {
+        println!("Skip {:?}: not a regular file, directory or link", path);
+        return;
+    };
 
-fn winode(sb: &SuperBlock, inum: u32, inode: Inode) {
-    let mut buf = [0; BLKSZ];
+    if name.len() > NAME_MAX {
+        panic!("{:?}: the name is longer than {} bytes", path, NAME_MAX);
+    }
 
-    // Read, write, and modify
-    let block = iblock(sb, inum);
-    rsect(block, &mut buf);
+    // The link is copied as it is, even if the target is not in image
+    if typ == T_SYMLINK {
+        let target =
+            host_fs::read_link(path).unwrap_or_else(|e| panic!("readlink {:?}: {}", path, e));
+        let target = target.to_str().expect("non UTF-8 link target");
+        let inum = fs
+            .symlink(dir, target, name)
+            .unwrap_or_else(|e| panic!("fail to link {:?}: {}", path, e));
+        println!("Add {:?} -> {:?} as inode {}", path, target, inum);
+        copy_attr(fs, inum, &meta);
+        return;
+    }
 
-    assert!(inum > 0);
+    let inum = fs
+        .create(dir, name, typ, 0, 0)
+        .unwrap_or_else(|| panic!("fail to create {:?}: invalid name or no space", path));
+    println!("Add {:?} as inode {}", path, inum);
 
-    let inode_ptr = block_inode(&mut buf, inum);
-    *inode_ptr = inode;
-    wsect(block, &buf);
+    if typ == T_DIR {
+        add_dir(fs, inum, path);
+    } else {
+        copy_file(fs, inum, path);
+    }
+    // After the content is added, which changes the mtime
+    copy_attr(fs, inum, &meta);
 }
 
-fn alloc_inode(sb: &SuperBlock, typ: u16) -> u32 {
-    let inum = NEXT_INUM.get();
-    NEXT_INUM.set(inum + 1);
-
-    let inode = Inode {
-        typ: typ,
-        major: 0,
-        minor: 0,
-        nlink: 1,
-        size: 0,
-        directs: [0; NDIRECT],
-        indirect: 0,
-    };
+// Add everything in the host directory under the directory of image
+fn add_dir(fs: &FileSystem<FileDevice>, dir: u32, path: &Path) {
+    let mut entries: Vec<_> = host_fs::read_dir(path)
+        .unwrap_or_else(|e| panic!("read_dir {:?}: {}", path, e))
+        .map(|entry| entry.expect("read_dir()").path())
+        .collect();
+    // Sort the entries, so we can always get the same image
+    entries.sort();
+
+    for entry in entries {
+        let name = entry
+            .file_name()
+            .unwrap()
+            .to_str()
+            .expect("non UTF-8 file name");
+        add_path(fs, dir, name, &entry);
+    }
+}
 
-    winode(sb, inum, inode);
-    inum
+fn usage(prog: &str) -> ! {
+    let geo = Geometry::default();
+    println!("Usage: {} [options] <image> [file or directory]...", prog);
+    println!("The content of directory is put under the root of image,");
+    println!("and the file is put under the root by its base name.");
+    println!();
+    println!("Options:");
+    println!(
+        "  -s, --size <blocks>    size of image (default {})",
+        geo.fs_blksz
+    );
+    println!(
+        "  -i, --inodes <count>   number of inodes (default {})",
+        geo.ninodes
+    );
+    println!(
+        "  -l, --log <blocks>     size of log (default {})",
+        geo.nlog
+    );
+    exit(1);
 }
 
-fn iappend(sb: &SuperBlock, inum: u32, data: &[u8]) {
-    /* Append new contents to the file described by this inode */
-    let mut buf = [0; BLKSZ];
-    let len = data.len();
-    let mut inode = rinode(sb, inum);
-
-    /* The new data will append from the end of file */
-    let end = inode.size as usize;
-    let mut off = 0;
-
-    while off < len {
-        let nlink = (end + off) / BLKSZ;
-        assert!(nlink < FILE_MAX_LINK);
-
-        let mut block_num;
-        if nlink < NDIRECT {
-            /* The first NDIRECT links are directly linked */
-            block_num = inode.directs[nlink];
-
-            if block_num == 0 {
-                block_num = ALLOC_BLOCK.get();
-                inode.directs[nlink] = block_num;
-                ALLOC_BLOCK.set(block_num + 1);
+/* Parse the options for the geometry of image, and return the remaining
+ * arguments. Return None if any option is invalid. */
+fn parse_args(args: &[String]) -> Option<(Geometry, &[String])> {
+    let mut geo = Geometry::default();
+    let mut i = 0;
+
+    while i < args.len() && args[i].starts_with('-') {
+        let field = match args[i].as_str() {
+            "-s" | "--size" => &mut geo.fs_blksz,
+            "-i" | "--inodes" => &mut geo.ninodes,
+            "-l" | "--log" => &mut geo.nlog,
+            opt => {
+                println!("Unknown option {}", opt);
+                return None;
+            }
+        };
+
+        let value = args.get(i + 1).and_then(|v| v.parse().ok());
+        match value {
+            Some(value) => *field = value,
+            None => {
+                println!("Option {} expects a number", args[i]);
+                return None;
             }
-        } else {
-            // TODO: Support larger file size which requires indirect linking
-            todo!();
         }
-
-        let n = len.min((nlink + 1) * BLKSZ - (end + off));
-        rsect(block_num, &mut buf);
-        let buf_start = (end + off) - nlink * BLKSZ;
-        buf[buf_start..buf_start + n].copy_from_slice(&data[off..off + n]);
-        wsect(block_num, &buf);
-
-        off += n;
+        i += 2;
     }
 
-    inode.size += len as u32;
-    winode(sb, inum, inode);
-}
-
-fn create_dent(sb: &SuperBlock, inum: u32, name: &str) {
-    let mut dirent: Dirent = unsafe { MaybeUninit::zeroed().assume_init() };
-    dirent.update(inum, name);
-    iappend(&sb, inum, as_slice(&dirent));
+    Some((geo, &args[i..]))
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let img_name = &args[1];
-
-    let mut buf = [0; BLKSZ];
-    FSFILE.set(
-        OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(img_name)
-            .expect("create()"),
-    );
+    let (geo, args) = match parse_args(&args[1..]) {
+        Some((geo, args)) if !args.is_empty() => (geo, args),
+        _ => usage(&args[0]),
+    };
 
-    for i in 0..FS_BLKSZ {
-        wsect(i as u32, &[0; BLKSZ]);
-    }
+    let img_name = &args[0];
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(img_name)
+        .expect("create()");
+    file.set_len(geo.fs_blksz as u64 * BLKSZ as u64)
+        .expect("set_len()");
 
-    /* In this design, we have the following metadata. Sequentially from
-     * the first block:
-     * - 1 block for boot block
-     * - 1 block for suberblock
-     * - LOG_BLKSZ count for log block
-     * - INODE_BLKSZ count for inode block
-     * - BITMAP_BLKSZ count for bitmap block */
-    assert!(BLKSZ % size_of::<Inode>() == 0);
-    let nmeta = 1 + 1 + LOG_BLKSZ + INODE_BLKSZ + BITMAP_BLKSZ;
-    // The remaining blocks are used for data block
-    let nblocks = FS_BLKSZ - nmeta;
-
-    // There are nmeta blocks allocated currently
-    ALLOC_BLOCK.set(nmeta as u32);
+    let fs = FileSystem::format(FileDevice::new(file), &geo)
+        .unwrap_or_else(|| panic!("invalid geometry {:?}", geo));
+    let sb = fs.sb();
 
     println!(
         "Total {} = 1 boot + 1 superblock + {} log + {} inode + {} bitmap + {} data",
-        FS_BLKSZ, LOG_BLKSZ, INODE_BLKSZ, BITMAP_BLKSZ, nblocks
+        sb.fs_blksz,
+        sb.nlog,
+        sb.inode_blksz(),
+        sb.bitmap_blksz(),
+        sb.nblocks
     );
 
-    let sb = SuperBlock {
-        magic: MAGIC,
-        fs_blksz: FS_BLKSZ,
-        nblocks: nblocks,
-        ninodes: NINODES,
-        nlog: LOG_BLKSZ,
-        logstart: 2,
-        inodestart: 2 + LOG_BLKSZ,
-        bmapstart: 2 + LOG_BLKSZ + INODE_BLKSZ,
-    };
-    buf[0..size_of::<SuperBlock>()].copy_from_slice(as_slice(&sb));
-    wsect(1, &mut buf);
-
-    /* Create inode for the root directory */
-    let rootino = alloc_inode(&sb, T_DIR);
-    assert!(rootino == ROOTINO);
-
-    /* Create root directory entry that the root inode refers to */
-    create_dent(&sb, rootino, ".");
-
-    /* Create parent directory entry */
-    create_dent(&sb, rootino, "..");
-
-    /* TODO: Append file under root directory(if any) */
-
-    /* Update bitmap for the allocated and unallocated blocks */
-    let total_used = ALLOC_BLOCK.get();
-    let mut buf = [0; BLKSZ];
-    /* We simply assume that the bitmap in the first block is enough for
-     * all the used data. */
-    assert!((total_used as usize) < BLKSZ * 8);
-    for i in 0..total_used {
-        buf[i as usize / 8] |= 1 << (i % 8);
+    for arg in &args[1..] {
+        let path = Path::new(arg);
+        if path.is_dir() {
+            add_dir(&fs, ROOTINO, path);
+        } else {
+            let name = path.file_name().and_then(|name| name.to_str());
+            let name = name.unwrap_or_else(|| panic!("invalid file name {:?}", path));
+            add_path(&fs, ROOTINO, name, path);
+        }
     }
-    wsect(sb.bmapstart, &buf);
 }
@@ -0,0 +1,20 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/* Prepare the kernel symbol table to be embedded in the image. It is
+ * generated by scripts/gen-ksyms from the kernel of the previous build,
+ * and its path is given by the KSYMS environment variable. An empty
+ * table is used if the file is not ready yet. */
+fn main() {
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("ksyms.bin");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=KSYMS");
+
+    let ksyms = env::var("KSYMS").ok().and_then(|path| {
+        println!("cargo:rerun-if-changed={}", path);
+        fs::read(path).ok()
+    });
+    fs::write(out, ksyms.unwrap_or_default()).unwrap();
+}
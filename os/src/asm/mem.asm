@@ -17,5 +17,11 @@ DATA_START: .dword _data_start
 .global BSS_START
 BSS_START: .dword _bss_start
 
+.global KSYMS_START
+KSYMS_START: .dword _ksyms_start
+
+.global KSYMS_END
+KSYMS_END: .dword _ksyms_end
+
 .global MTRAP_STACK_END
 MTRAP_STACK_END: .dword _mtrap_stack_end
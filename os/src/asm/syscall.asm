@@ -5,6 +5,37 @@
 .set SYS_read, 63
 .set SYS_write, 64
 .set SYS_mknod, 33
+.set SYS_getcwd, 17
+.set SYS_chdir, 49
+.set SYS_fchdir, 50
+.set SYS_mkdir, 34
+.set SYS_unlink, 35
+.set SYS_link, 37
+.set SYS_rename, 38
+.set SYS_symlink, 36
+.set SYS_readlink, 78
+.set SYS_stat, 79
+.set SYS_fstat, 80
+.set SYS_chmod, 53
+.set SYS_chown, 54
+.set SYS_utimes, 88
+.set SYS_getdents64, 61
+.set SYS_lseek, 62
+.set SYS_pread64, 67
+.set SYS_pwrite64, 68
+.set SYS_truncate, 45
+.set SYS_ftruncate, 46
+.set SYS_clock_gettime, 113
+.set SYS_gettimeofday, 169
+.set SYS_uptime, 1000
+.set SYS_kill, 129
+.set SYS_rt_sigaction, 134
+.set SYS_rt_sigprocmask, 135
+.set SYS_setpgid, 154
+.set SYS_getpgid, 155
+.set SYS_ioctl, 29
+.set SYS_syslog, 116
+.set SYS_reboot, 142
 
 .section .text.user
 .global open
@@ -13,6 +44,13 @@ open:
     ecall
     ret
 
+.section .text.user
+.global close
+close:
+    li a7, SYS_close
+    ecall
+    ret
+
 .section .text.user
 .global write
 write:
@@ -26,3 +64,237 @@ mknod:
     li a7, SYS_mknod
     ecall
     ret
+
+.section .text.user
+.global clock_gettime
+clock_gettime:
+    li a7, SYS_clock_gettime
+    ecall
+    ret
+
+.section .text.user
+.global gettimeofday
+gettimeofday:
+    li a7, SYS_gettimeofday
+    ecall
+    ret
+
+.section .text.user
+.global uptime
+uptime:
+    li a7, SYS_uptime
+    ecall
+    ret
+
+.section .text.user
+.global kill
+kill:
+    li a7, SYS_kill
+    ecall
+    ret
+
+.section .text.user
+.global rt_sigaction
+rt_sigaction:
+    li a7, SYS_rt_sigaction
+    ecall
+    ret
+
+.section .text.user
+.global rt_sigprocmask
+rt_sigprocmask:
+    li a7, SYS_rt_sigprocmask
+    ecall
+    ret
+
+.section .text.user
+.global read
+read:
+    li a7, SYS_read
+    ecall
+    ret
+
+.section .text.user
+.global setpgid
+setpgid:
+    li a7, SYS_setpgid
+    ecall
+    ret
+
+.section .text.user
+.global getpgid
+getpgid:
+    li a7, SYS_getpgid
+    ecall
+    ret
+
+.section .text.user
+.global ioctl
+ioctl:
+    li a7, SYS_ioctl
+    ecall
+    ret
+
+.section .text.user
+.global syslog
+syslog:
+    li a7, SYS_syslog
+    ecall
+    ret
+
+.section .text.user
+.global reboot
+reboot:
+    li a7, SYS_reboot
+    ecall
+    ret
+
+.section .text.user
+.global getcwd
+getcwd:
+    li a7, SYS_getcwd
+    ecall
+    ret
+
+.section .text.user
+.global chdir
+chdir:
+    li a7, SYS_chdir
+    ecall
+    ret
+
+.section .text.user
+.global fchdir
+fchdir:
+    li a7, SYS_fchdir
+    ecall
+    ret
+
+.section .text.user
+.global mkdir
+mkdir:
+    li a7, SYS_mkdir
+    ecall
+    ret
+
+.section .text.user
+.global unlink
+unlink:
+    li a1, 0
+    li a7, SYS_unlink
+    ecall
+    ret
+
+# rmdir is unlink with AT_REMOVEDIR
+.section .text.user
+.global rmdir
+rmdir:
+    li a1, 0x200
+    li a7, SYS_unlink
+    ecall
+    ret
+
+.section .text.user
+.global link
+link:
+    li a7, SYS_link
+    ecall
+    ret
+
+.section .text.user
+.global rename
+rename:
+    li a7, SYS_rename
+    ecall
+    ret
+
+.section .text.user
+.global symlink
+symlink:
+    li a7, SYS_symlink
+    ecall
+    ret
+
+.section .text.user
+.global readlink
+readlink:
+    li a7, SYS_readlink
+    ecall
+    ret
+
+.section .text.user
+.global stat
+stat:
+    li a7, SYS_stat
+    ecall
+    ret
+
+.section .text.user
+.global fstat
+fstat:
+    li a7, SYS_fstat
+    ecall
+    ret
+
+.section .text.user
+.global chmod
+chmod:
+    li a7, SYS_chmod
+    ecall
+    ret
+
+.section .text.user
+.global chown
+chown:
+    li a7, SYS_chown
+    ecall
+    ret
+
+.section .text.user
+.global utimes
+utimes:
+    li a7, SYS_utimes
+    ecall
+    ret
+
+.section .text.user
+.global getdents64
+getdents64:
+    li a7, SYS_getdents64
+    ecall
+    ret
+
+.section .text.user
+.global lseek
+lseek:
+    li a7, SYS_lseek
+    ecall
+    ret
+
+.section .text.user
+.global pread
+pread:
+    li a7, SYS_pread64
+    ecall
+    ret
+
+.section .text.user
+.global pwrite
+pwrite:
+    li a7, SYS_pwrite64
+    ecall
+    ret
+
+.section .text.user
+.global truncate
+truncate:
+    li a7, SYS_truncate
+    ecall
+    ret
+
+.section .text.user
+.global ftruncate
+ftruncate:
+    li a7, SYS_ftruncate
+    ecall
+    ret
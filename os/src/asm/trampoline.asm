@@ -60,3 +60,11 @@ userret:
     csrw    sscratch, t5
 
     sret
+
+.section .text.sigtrampoline
+.globl sigtrampoline
+sigtrampoline:
+    # The signal handler returns here, then we invoke
+    # rt_sigreturn(=139) to restore the context before signal
+    li      a7, 139
+    ecall
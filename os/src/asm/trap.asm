@@ -61,7 +61,8 @@ kernelvec:
        .set       i, i+1
     .endr
 
-    # jump to kernel trap handler
+    # jump to kernel trap handler with the saved registers
+    mv   a0, sp
     call kernel_trap_handler
 
     .set     i, 0
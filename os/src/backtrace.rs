@@ -0,0 +1,90 @@
+/* Stack unwinding by the frame pointer. With frame pointer enabled, the
+ * prologue of each function saves the return address at fp - 8 and the
+ * frame pointer of caller at fp - 16, so we can walk through the frames
+ * as a linked list. */
+use crate::config::{DRAM_BASE, HIGH_MEMORY};
+use core::arch::{asm, global_asm};
+use core::fmt;
+use core::slice;
+
+// The symbol table is prepared by build.rs
+global_asm!(concat!(
+    ".section .ksyms, \"a\"\n",
+    ".incbin \"",
+    env!("OUT_DIR"),
+    "/ksyms.bin\"\n"
+));
+
+// Stop unwinding if the stack is corrupted to loop forever
+const MAX_DEPTH: usize = 32;
+
+/* Find the symbol which the address belongs to, and return its name
+ * with the offset from the start of it. */
+fn lookup(addr: usize) -> Option<(&'static str, usize)> {
+    extern "C" {
+        static KSYMS_START: usize;
+        static KSYMS_END: usize;
+    }
+
+    let ksyms = unsafe {
+        let len = KSYMS_END - KSYMS_START;
+        slice::from_raw_parts(KSYMS_START as *const u8, len)
+    };
+    let ksyms = core::str::from_utf8(ksyms).ok()?;
+
+    let mut sym = None;
+    // The symbols are sorted by their address
+    for line in ksyms.lines() {
+        let (start, name) = line.split_once(' ')?;
+        let start = usize::from_str_radix(start, 16).ok()?;
+        if start > addr {
+            break;
+        }
+        sym = Some((name, addr - start));
+    }
+    sym
+}
+
+// Display the address with the symbol it belongs to
+pub struct Symbol(pub usize);
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match lookup(self.0) {
+            Some((name, offset)) => write!(f, "{:#x} <{}+{:#x}>", self.0, name, offset),
+            None => write!(f, "{:#x} <unknown>", self.0),
+        }
+    }
+}
+
+fn is_valid_fp(fp: usize) -> bool {
+    fp % 8 == 0 && fp > DRAM_BASE + 16 && fp <= HIGH_MEMORY
+}
+
+// Print the call stack from the caller of this function
+#[inline(never)]
+pub fn backtrace() {
+    let mut fp: usize;
+    unsafe {
+        asm!("mv {}, s0", out(reg) fp);
+    }
+
+    panic_println!("backtrace:");
+    for depth in 0..MAX_DEPTH {
+        if !is_valid_fp(fp) {
+            break;
+        }
+
+        let (ra, prev_fp) = unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        if ra == 0 {
+            break;
+        }
+
+        /* The return address points to the instruction after the call,
+         * which may belong to the next function if the call is the last
+         * instruction. Look up the call itself instead. */
+        panic_println!("  #{} {}", depth, Symbol(ra - 4));
+
+        fp = prev_fp;
+    }
+}
@@ -1,11 +1,46 @@
-/* The frequency given by QEMU is 10_000_000 Hz, thus
- * the interval 2_500_000 means that we tick every 250ms
- * to switch our task. */
-const INTERVAL: usize = 2_500_000;
+use crate::config::{CLOCK_FREQ, TICK_HZ};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/* The interval between two timer ticks, which is decided by
+ * the tick rate we choose in compile time. For example, if we
+ * tick in 100 Hz, the interval will be 100_000 and we tick every
+ * 10ms to switch our task. */
+const INTERVAL: usize = CLOCK_FREQ / TICK_HZ;
 
 mmap_reg!(mtimecmp, 0x200_0000 + 0x4000, usize);
 mmap_reg!(mtime, 0x200_0000 + 0xbff8, usize);
 
+/* Whether the periodic tick is stopped. This is shared with the machine
+ * mode timer handler, so we can't protect it with Locked which relies on
+ * the supervisor interrupt. */
+static TICK_STOPPED: AtomicBool = AtomicBool::new(false);
+
+pub fn get_time() -> usize {
+    mtime::read()
+}
+
 pub fn set_next_tick() {
-    mtimecmp::write(mtime::read() + INTERVAL);
+    if TICK_STOPPED.load(Ordering::Acquire) {
+        /* The pending timer has expired if we are here, and the
+         * scheduler will restart the tick for the next task. Push the
+         * deadline to the far future so we won't be interrupted again. */
+        mtimecmp::write(usize::MAX);
+    } else {
+        mtimecmp::write(mtime::read() + INTERVAL);
+    }
+}
+
+/* Stop the periodic tick and only arrange the timer interrupt for the
+ * given deadline(if any). This is used when the CPU is going to idle, so
+ * it won't be woken up by a tick which has nothing to do. */
+pub fn tick_stop(deadline: Option<usize>) {
+    TICK_STOPPED.store(true, Ordering::Release);
+    mtimecmp::write(deadline.unwrap_or(usize::MAX));
+}
+
+// Restart the periodic tick if it is stopped
+pub fn tick_restart() {
+    if TICK_STOPPED.swap(false, Ordering::AcqRel) {
+        set_next_tick();
+    }
 }
@@ -19,6 +19,23 @@ pub const UART_BASE: usize = 0x1000_0000;
 pub const CLINT_BASE: usize = 0x200_0000;
 // 64K mapping region for CLINT
 pub const CLINT_SIZE: usize = 0x10000;
+// The frequency of mtime given by QEMU is 10_000_000 Hz
+pub const CLOCK_FREQ: usize = 10_000_000;
+// The number of timer tick per second, which is selected by cargo feature
+#[cfg(feature = "hz1000")]
+pub const TICK_HZ: usize = 1000;
+#[cfg(all(feature = "hz250", not(feature = "hz1000")))]
+pub const TICK_HZ: usize = 250;
+#[cfg(not(any(feature = "hz250", feature = "hz1000")))]
+pub const TICK_HZ: usize = 100;
+// The test finisher of QEMU start from 0x100000
+pub const FINISHER_BASE: usize = 0x10_0000;
+// mapping region for the test finisher
+pub const FINISHER_SIZE: usize = 0x1000;
+// Goldfish RTC start from 0x101000
+pub const RTC_BASE: usize = 0x10_1000;
+// mapping region for RTC
+pub const RTC_SIZE: usize = 0x1000;
 // PLIC start from 0xc000000
 pub const PLIC_BASE: usize = 0xc00_0000;
 // mapping region for PLIC
@@ -35,6 +52,8 @@ pub const TASK_START_ADDR: usize = 0x1000;
 pub const STACK_TOP_ADDR: usize = 0xa000_0000;
 // The start virtual address for kernel
 pub const KERNEL_START_VA: usize = 0xc000_0000;
+// User space maps the signal trampoline to return from signal handler
+pub const SIGTRAMPOLINE_VA: usize = TRAPFRAME_VA - PAGE_SIZE;
 // Both kernel and user space map trap frame in the same address to handle trap
 pub const TRAPFRAME_VA: usize = TRAMPOLINE_VA - PAGE_SIZE;
 // Both kernel and user space map trampoline in the same address to handle trap
@@ -1,4 +1,6 @@
-use crate::uart::uart_put;
+use crate::cpu;
+use crate::lock::Locked;
+use crate::uart::{uart_flush_sync, uart_put, uart_put_sync};
 use core::fmt::{self, Error, Write};
 
 struct Stdout;
@@ -12,36 +14,63 @@ impl Write for Stdout {
     }
 }
 
+/* The output is synchronized by the lock, so the messages from
+ * different context won't interleave with each other. */
+static STDOUT: Locked<Stdout> = Locked::new(Stdout);
+
+struct SyncStdout;
+
+impl Write for SyncStdout {
+    fn write_str(&mut self, out: &str) -> Result<(), Error> {
+        for c in out.bytes() {
+            uart_put_sync(c);
+        }
+        Ok(())
+    }
+}
+
 pub fn print(args: fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    /* The console is also used in interrupt handler, so we
+     * should disable interrupt when holding the lock to avoid
+     * deadlock. */
+    let intr = cpu::intr_save();
+    STDOUT.lock().write_fmt(args).unwrap();
+    cpu::intr_restore(intr);
 }
 
-macro_rules! print {
-    ($fmt: literal $(, $($arg: tt)+)?) => {
-        $crate::console::print(format_args!($fmt $(, $($arg)+)?));
+// Write the raw bytes, which may not be valid UTF-8, to console
+pub fn write(buf: &[u8]) {
+    let intr = cpu::intr_save();
+    let stdout = STDOUT.lock();
+    for c in buf {
+        uart_put(*c);
     }
+    drop(stdout);
+    cpu::intr_restore(intr);
 }
 
-macro_rules! println {
-    ($fmt: literal $(, $($arg: tt)+)?) => {
-        $crate::console::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?));
-    }
+/* Print without buffering and locking, which should be used in the
+ * panic context only. The buffered output is flushed first to keep the
+ * order of messages. */
+pub fn print_sync(args: fmt::Arguments) {
+    uart_flush_sync();
+    SyncStdout.write_fmt(args).unwrap();
 }
 
-macro_rules! dbg {
+macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
-        $crate::console::print(format_args!(concat!("\x1b[0;32m", $fmt, "\x1b[0m\n") $(, $($arg)+)?));
+        $crate::console::print(format_args!($fmt $(, $($arg)+)?));
     }
 }
 
-macro_rules! info {
+macro_rules! println {
     ($fmt: literal $(, $($arg: tt)+)?) => {
-        $crate::console::print(format_args!(concat!("\x1b[1;94m", $fmt, "\x1b[0m\n") $(, $($arg)+)?));
+        $crate::console::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?));
     }
 }
 
-macro_rules! warning {
+macro_rules! panic_println {
     ($fmt: literal $(, $($arg: tt)+)?) => {
-        $crate::console::print(format_args!(concat!("\x1b[1;33m", $fmt, "\x1b[0m\n") $(, $($arg)+)?));
+        $crate::console::print_sync(format_args!(concat!($fmt, "\n") $(, $($arg)+)?));
     }
 }
@@ -1,5 +1,5 @@
 use core::arch::asm;
-use riscv::register::{sie, sstatus};
+use riscv::register::sstatus;
 
 pub fn intr_on() {
     unsafe { sstatus::set_sie() };
@@ -9,9 +9,26 @@ pub fn intr_off() {
     unsafe { sstatus::clear_sie() };
 }
 
-pub fn timer_on() {
-    unsafe { sie::set_stimer() };
-    intr_on();
+pub fn intr_get() -> bool {
+    sstatus::read().sie()
+}
+
+// Disable interrupt and return whether it is enabled before
+pub fn intr_save() -> bool {
+    let intr = intr_get();
+    intr_off();
+    intr
+}
+
+// Restore the interrupt to the state returned by intr_save()
+pub fn intr_restore(intr: bool) {
+    if intr {
+        intr_on();
+    }
+}
+
+pub fn wfi() {
+    unsafe { asm!("wfi") };
 }
 
 pub fn w_sip(val: usize) {
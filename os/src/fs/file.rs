@@ -0,0 +1,82 @@
+use core::convert::TryFrom;
+use core::ffi::c_int;
+
+// Maximum number of open files per process
+pub const NOFILE: usize = 16;
+
+/* The open file, which refers to an inode with the offset for the next
+ * read or write. For directory, the offset is the position of next entry
+ * in it, so the listing can be resumed. The inode is counted by
+ * open_file() as long as the file is in a table. */
+#[derive(Clone, Copy, Debug)]
+pub struct File {
+    pub inum: u32,
+    pub off: usize,
+    // The flags given to open
+    pub flags: c_int,
+}
+
+impl File {
+    pub fn new(inum: u32, flags: c_int) -> Self {
+        File {
+            inum,
+            off: 0,
+            flags,
+        }
+    }
+}
+
+// The open files of a process, indexed by the file descriptor
+pub struct FileTable {
+    files: [Option<File>; NOFILE],
+}
+
+impl FileTable {
+    pub fn new() -> Self {
+        FileTable {
+            files: [None; NOFILE],
+        }
+    }
+
+    /* Install the file at the lowest free descriptor, and return the
+     * descriptor. Return None if too many files are opened. */
+    pub fn alloc(&mut self, file: File) -> Option<c_int> {
+        let fd = self.files.iter().position(|f| f.is_none())?;
+        self.files[fd] = Some(file);
+        Some(fd as c_int)
+    }
+
+    pub fn get(&mut self, fd: c_int) -> Option<&mut File> {
+        let fd = usize::try_from(fd).ok()?;
+        self.files.get_mut(fd)?.as_mut()
+    }
+
+    // Remove the file from the table, and return it if it was opened
+    pub fn close(&mut self, fd: c_int) -> Option<File> {
+        let fd = usize::try_from(fd).ok()?;
+        self.files.get_mut(fd)?.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn alloc_lowest_fd() {
+        let mut table = FileTable::new();
+        assert_eq!(table.alloc(File::new(1, 0)), Some(0));
+        assert_eq!(table.alloc(File::new(2, 0)), Some(1));
+        assert_eq!(table.close(0).map(|f| f.inum), Some(1));
+        assert!(table.close(0).is_none());
+        assert!(table.get(-1).is_none());
+
+        // The closed descriptor is reused first
+        assert_eq!(table.alloc(File::new(3, 0)), Some(0));
+        for fd in 2..NOFILE {
+            assert_eq!(table.alloc(File::new(4, 0)), Some(fd as c_int));
+        }
+        assert!(table.alloc(File::new(5, 0)).is_none());
+        assert_eq!(table.get(1).map(|f| f.inum), Some(2));
+    }
+}
@@ -1,27 +1,99 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
 use core::ffi::c_int;
-use core::mem::{size_of, MaybeUninit};
-use core::ptr;
 
 use crate::bio::*;
 use crate::lock::Locked;
-use crate::utils::cast::*;
-use crate::utils::cstr::*;
+use crate::mm::mapping::Mapping;
+use crate::time;
 
-use alloc::vec;
 use fs::*;
 use lazy_static::lazy_static;
 
-// Maximum length for the name of file
-pub const MAXPATH: usize = 128;
+mod file;
+
+pub use file::*;
+
+// Maximum size of file in bytes
+pub const MAX_FILE_SIZE: usize = FILE_MAX_LINK * BLKSZ;
+
+// Maximum length for the path of file, including the trailing '\0'
+pub const MAXPATH: usize = 4096;
 
 pub const O_RDONLY: c_int = 0x000;
 pub const O_WRONLY: c_int = 0x001;
 pub const O_RDWR: c_int = 0x002;
+// Fail with EEXIST if the file exists when it is created
+pub const O_EXCL: c_int = 0x100;
 pub const O_CREATE: c_int = 0x200;
 pub const O_TRUNC: c_int = 0x400;
+// Fail with ELOOP if the last element of path is a symbolic link
+pub const O_NOFOLLOW: c_int = 0x800;
+// Every write goes to the end of file
+pub const O_APPEND: c_int = 0x1000;
+
+// The flag of unlinkat to remove the directory
+pub const AT_REMOVEDIR: c_int = 0x200;
+
+// The virtio disk which the root filesystem is on
+pub struct Disk;
+
+impl BlockDevice for Disk {
+    fn read_block(&self, block_no: u32, buf: &mut [u8]) {
+        bread(block_no, buf);
+    }
+
+    fn write_block(&self, block_no: u32, buf: &[u8]) {
+        bwrite(block_no, buf);
+    }
+}
 
 lazy_static! {
-    static ref SB: Locked<SuperBlock> = Locked::new(unsafe { MaybeUninit::zeroed().assume_init() });
+    static ref FS: FileSystem<Disk> = FileSystem::open(Disk)
+        .unwrap_or_else(|e| {
+            error!("Invalid root filesystem: {}", e);
+            panic!("Fail to mount the root filesystem");
+        })
+        .with_clock(time::realtime_ns)
+        .with_open_check(is_open);
+}
+
+/* The number of open files of each inode in all processes, and the cwd
+ * of a process is counted as well. The inode without any link is kept
+ * until its last open file is closed. */
+static OPEN_COUNT: Locked<BTreeMap<u32, usize>> = Locked::new(BTreeMap::new());
+
+fn is_open(inum: u32) -> bool {
+    OPEN_COUNT.lock().contains_key(&inum)
+}
+
+// Count a new open file of the inode
+pub fn open_file(inum: u32) {
+    *OPEN_COUNT.lock().entry(inum).or_insert(0) += 1;
+}
+
+/* Drop an open file of the inode, and release the inode if it is the
+ * last open file and the inode has been unlinked. */
+pub fn close_file(inum: u32) {
+    let last = {
+        let mut count = OPEN_COUNT.lock();
+        let n = count.get_mut(&inum).expect("close the inode not opened");
+        *n -= 1;
+        *n == 0 && count.remove(&inum).is_some()
+    };
+
+    if last && FS.release_orphan(inum) {
+        dbg!("Release orphan inode, inum={}", inum);
+    }
+}
+
+// Close all the open files in the table, e.g. when the process exits
+pub fn close_files(files: &mut FileTable) {
+    for fd in 0..NOFILE {
+        if let Some(file) = files.close(fd as c_int) {
+            close_file(file.inum);
+        }
+    }
 }
 
 /* The containter of Inode. It includes not only
@@ -42,310 +114,330 @@ impl Drop for FsInode {
          * FIXME: Consider the case when we have two inode cache
          * for the same inum, they will race and this simple
          * synchronization will fail. */
-        let mut inodes = vec![0; BLKSZ];
-        let inum = self.inum;
-        bread(iblock(&SB.lock(), inum), &inodes);
-
-        *block_inode(&mut inodes, inum) = self.inner;
+        FS.write_inode(self.inum, &self.inner);
 
-        bwrite(iblock(&SB.lock(), inum), &inodes);
-
-        dbg!("Release inode, inum={}", inum);
+        dbg!("Release inode, inum={}", self.inum);
     }
 }
 
 pub fn init() {
-    // Block 1 is where the SuperBlock located at
-    let mut buf = vec![0; BLKSZ];
-    bread(1, &mut buf);
+    lazy_static::initialize(&FS);
 
-    *SB.lock() = *to_struct::<SuperBlock>(&buf);
-}
-
-// Seperate the first path entry from the path string
-fn parse_path<'a>(path: &'a str) -> Option<(&'a str, &'a str)> {
-    // TODO: The implementation should be fixed to meet the expected result
-    if path.len() == 0 {
-        return None;
-    }
-
-    let path = path.trim();
-    if let Some(result) = path.split_once('/') {
-        Some(result)
-    } else {
-        Some((path, ""))
-    }
+    let sb = FS.sb();
+    info!(
+        "rfs v{}: {} blocks, {} inodes, {} log blocks, {} data blocks",
+        sb.version, sb.fs_blksz, sb.ninodes, sb.nlog, sb.nblocks
+    );
 }
 
 // Find the corresponding inode by inode number
 pub fn find_inode(inum: u32) -> FsInode {
     dbg!("Get inode, inum={}", inum);
 
-    let mut inodes = vec![0; BLKSZ];
-    bread(iblock(&SB.lock(), inum), &inodes);
-
     /* TODO: Optimize by implementing cache for Inode, so we don't need to
-     * traverse for the result every time. */
+     * read the disk for the result every time. */
     FsInode {
-        inner: *block_inode(&mut inodes, inum),
+        inner: FS.read_inode(inum),
         inum,
     }
 }
 
-pub fn alloc_inode(typ: u16, major: u16, minor: u16, nlink: u16) -> u32 {
-    let mut inodes = vec![0; BLKSZ];
-
-    /* Linear checking every inode in every inode block for the
-     * inode that is marked as non-allocated. */
-    for iblock_no in 0..INODE_BLKSZ {
-        let iblock = SB.lock().inodestart + iblock_no;
-        bread(iblock, &mut inodes);
-
-        for i in 0..(INODES_PER_BLK as u32) {
-            let inum = 1 + i + iblock_no * INODES_PER_BLK as u32;
-            let inode_ptr = block_inode(&mut inodes, inum);
-
-            if inode_ptr.is_free() {
-                inode_ptr.init(typ, major, minor, nlink);
-                bwrite(iblock, &inodes);
-                dbg!("Alloc inode, inum={}", inum);
-                return inum;
-            }
-        }
-    }
-
-    panic!("alloc_inode() fail: no empty inode");
+// Get the type of inode without holding it
+pub fn inode_type(inum: u32) -> u16 {
+    FS.read_inode(inum).typ
 }
 
-pub fn free_inode(mut fsinode: FsInode) {
-    fsinode.inner.set_free();
+/* Read the entry at the offset of directory, and the next entry is at
+ * `off + rec_len`. Return None at the end of directory. */
+pub fn read_dirent(fsinode: &FsInode, off: usize) -> Option<Dirent> {
+    FS.read_dirent(&fsinode.inner, off)
 }
 
-// Get the block number for the request data offset
-fn find_block(inode: &Inode, off: usize) -> u32 {
-    let block_off = off / BLKSZ;
-
-    // For the first NDIRECT blocks, they are direct linked
-    let block_no = if block_off < NDIRECT {
-        inode.directs[block_off]
-    } else {
-        todo!("find_block() indirect");
-    };
-
-    block_no
+// Get the offset of the first entry at or after the offset of directory
+pub fn dirent_boundary(fsinode: &FsInode, off: usize) -> usize {
+    FS.dirent_boundary(&fsinode.inner, off)
 }
 
-// Get the block number for the request data offset
-fn find_or_alloc_block(inode: &mut Inode, off: usize) -> u32 {
-    let block_no = find_block(inode, off);
-    if block_no != 0 {
-        return block_no;
-    }
-
-    /* If there is no corresponding block on this link, allocating
-     * one for it. */
-    let block_no = alloc_block();
-    let block_off = off / BLKSZ;
-
-    if block_off < NDIRECT {
-        inode.directs[block_off] = block_no;
-    } else {
-        todo!("find_or_alloc_block() indirect");
+/* Read data from inode directly to the user buffer at `addr` of the
+ * mapping, and return the number of bytes which are read. It is short
+ * at the end of file. Return None if the user buffer is not writable
+ * before anything is read. The atime is updated on success. */
+pub fn readi_user(
+    fsinode: &mut FsInode,
+    off: usize,
+    mm: &Mapping,
+    addr: usize,
+    len: usize,
+) -> Option<usize> {
+    let mut total = 0;
+    while total < len {
+        let dst = addr.checked_add(total);
+        let dst = match dst.and_then(|dst| mm.user_slice_mut(dst, len - total)) {
+            Some(dst) => dst,
+            None if total == 0 => return None,
+            None => break,
+        };
+
+        let n = FS.readi(&fsinode.inner, off + total, dst);
+        total += n;
+        if n < dst.len() {
+            break;
+        }
     }
 
-    block_no
+    fsinode.inner.atime = FS.now();
+    Some(total)
 }
 
-fn alloc_block() -> u32 {
-    let mut bitmap = vec![0; BLKSZ];
-
-    /* Linear checking every bit in every bitmap block for the
-     * block that is marked as non-allocated. */
-    for bmap_no in 0..BITMAP_BLKSZ {
-        let bmap_block = SB.lock().bmapstart + bmap_no;
-        bread(bmap_block, &mut bitmap);
-
-        for bit in 0..(BIT_PER_BLK as u32) {
-            let bytes = bit as usize / 8;
-            let mask = 1 << (bit % 8);
-            if bitmap[bytes] & mask == 0 {
-                bitmap[bytes] |= mask;
-                bwrite(bmap_block, &bitmap);
-                return bmap_no * BIT_PER_BLK as u32 + bit;
-            }
+/* Write data from the user buffer at `addr` of the mapping directly to
+ * inode, and return the number of bytes which are written. It is short
+ * if the disk is full or the file reaches the maximum size. Return None
+ * if the user buffer is not readable before anything is written. */
+pub fn writei_user(
+    fsinode: &mut FsInode,
+    off: usize,
+    mm: &Mapping,
+    addr: usize,
+    len: usize,
+) -> Option<usize> {
+    let len = len.min(MAX_FILE_SIZE.saturating_sub(off));
+    let mut total = 0;
+    while total < len {
+        let src = addr.checked_add(total);
+        let src = match src.and_then(|src| mm.user_slice(src, len - total)) {
+            Some(src) => src,
+            None if total == 0 => return None,
+            None => break,
+        };
+
+        let n = FS.writei(&mut fsinode.inner, off + total, src).unwrap_or(0);
+        total += n;
+        if n < src.len() {
+            break;
         }
     }
 
-    panic!("alloc_block() fail: no empty block");
+    Some(total)
 }
 
-// Read data from Inode
-fn readi<T>(fsinode: &FsInode, mut off: usize, dst: &mut T) -> bool {
-    let inode = &fsinode.inner;
+// Count the blocks allocated to inode, so the hole in file is excluded
+pub fn nblocks(fsinode: &FsInode) -> usize {
+    FS.nblocks(&fsinode.inner)
+}
 
-    if off > inode.size as usize {
-        return false;
-    }
+// Change the size of file, and the blocks beyond it are released
+pub fn truncate(fsinode: &mut FsInode, size: usize) -> bool {
+    FS.truncate(&mut fsinode.inner, size)
+}
 
-    let mut total = 0;
-    let size = size_of::<T>();
-    let size = size.min(inode.size as usize - off);
-    let buf = vec![0; BLKSZ];
-
-    while total < size {
-        let block_num = find_block(inode, off);
-        assert!(block_num != 0);
-
-        bread(block_num, &buf);
-        let n = (size - total).min(BLKSZ - off % BLKSZ);
-
-        // FIXME: Is it possible to make this safe?
-        unsafe {
-            let src_ptr = buf.as_ptr().add(off % BLKSZ);
-            let mut dst_ptr = dst as *mut T as *mut u8;
-            dst_ptr = dst_ptr.add(total);
-            ptr::copy_nonoverlapping(src_ptr, dst_ptr, n);
-        }
+/* Find the corresponding inode by the path, with the reason if it fails.
+ * The relative path is started from the directory `cwd`, and the last
+ * symbolic link is followed if `follow` is true. */
+pub fn lookup_inode(cwd: u32, path: &str, follow: bool) -> Result<FsInode, Error> {
+    FS.lookup(cwd, path, follow).map(find_inode)
+}
 
-        total += n;
-        off += n;
-    }
+/* Find the inode of the parent directory by the path, with the name of
+ * the last path element. */
+pub fn path_to_parent<'a>(cwd: u32, path: &'a str) -> Option<(FsInode, &'a str)> {
+    dbg!("Traslate path {} to parent inode", path);
 
-    true
+    FS.nameiparent(cwd, path)
+        .map(|(inum, name)| (find_inode(inum), name))
 }
 
-// Write data to Inode
-fn writei<T>(fsinode: &mut FsInode, mut off: usize, src: &T) -> bool {
-    let inode = &mut fsinode.inner;
-    let size = size_of::<T>();
+// Get the absolute path of the directory
+pub fn inode_path(inum: u32) -> Option<String> {
+    FS.dirpath(inum)
+}
 
-    /* The off should only < size to override data in inode,
-     * or = size to append data in inode */
-    if off > inode.size as usize {
-        return false;
-    }
+/* The operations on the namespace. The inodes touched by them should not
+ * be held as FsInode by the caller, or they will be overwritten when the
+ * FsInode is dropped. */
+pub fn mkdir(cwd: u32, path: &str) -> Result<u32, Error> {
+    FS.mkdir(cwd, path)
+}
 
-    if (off + size) > (FILE_MAX_LINK * BLKSZ) {
-        return false;
-    }
+// Create a regular file or device at the path
+pub fn mknod(cwd: u32, path: &str, typ: u16, major: u16, minor: u16) -> Result<u32, Error> {
+    FS.mknod(cwd, path, typ, major, minor)
+}
 
-    let mut total = 0;
-    let mut buf = vec![0; BLKSZ];
-
-    while total < size {
-        let block_num = find_block(inode, off);
-        assert!(block_num != 0);
-
-        bread(block_num, &buf);
-        let n = (size - total).min(BLKSZ - off % BLKSZ);
-
-        // FIXME: Is it possible to make this safe?
-        unsafe {
-            let mut src_ptr = src as *const T as *const u8;
-            src_ptr = src_ptr.add(total);
-            let mut dst_ptr = buf.as_mut_ptr();
-            dst_ptr = dst_ptr.add(off % BLKSZ);
-            ptr::copy_nonoverlapping(src_ptr, dst_ptr, n);
-            // Write back
-            bwrite(block_num, &buf);
-        }
+pub fn unlink(cwd: u32, path: &str) -> Result<(), Error> {
+    FS.unlink(cwd, path)
+}
 
-        total += n;
-        off += n;
-    }
+pub fn rmdir(cwd: u32, path: &str) -> Result<(), Error> {
+    FS.rmdir(cwd, path)
+}
 
-    if off > inode.size as usize {
-        inode.size = off as u32;
-    }
+pub fn link(cwd: u32, old: &str, new: &str) -> Result<(), Error> {
+    FS.link(cwd, old, new)
+}
 
-    true
+pub fn rename(cwd: u32, old: &str, new: &str) -> Result<(), Error> {
+    FS.rename(cwd, old, new)
 }
 
-// Find the directory's Inode and its number under current Inode
-pub fn dirlookup(fsinode: &FsInode, name: &str) -> Option<FsInode> {
-    let inode = &fsinode.inner;
-    assert!(inode.typ == T_DIR);
+pub fn symlink(cwd: u32, target: &str, path: &str) -> Result<u32, Error> {
+    FS.symlink(cwd, target, path)
+}
 
-    for off in (0..inode.size as usize).step_by(size_of::<Dirent>()) {
-        let mut dirent: Dirent = unsafe { MaybeUninit::zeroed().assume_init() };
-        if !readi(fsinode, off, &mut dirent) {
-            return None;
-        }
+pub fn readlink(cwd: u32, path: &str) -> Result<String, Error> {
+    FS.readlink(cwd, path)
+}
 
-        // This dirent contain nothing
-        if dirent.inum == 0 {
-            continue;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PAGE_SIZE;
+    use crate::mm::mapping::{PteFlag, Segment};
+    use crate::mm::page;
 
-        let s = buf2cstr(dirent.name.to_vec());
-        if name == s {
-            return Some(find_inode(dirent.inum as u32));
-        }
+    #[test_case]
+    fn superblock_loaded() {
+        assert_eq!(FS.sb().magic, MAGIC);
     }
 
-    None
-}
-
-pub fn dirlink(fsinode: &mut FsInode, name: &str, inum: u32) -> bool {
-    if dirlookup(fsinode, name).is_some() {
-        return false;
+    #[test_case]
+    fn lookup_root() {
+        let root = lookup_inode(ROOTINO, "/", true).unwrap();
+        assert_eq!(root.inum, ROOTINO);
+        assert_eq!(root.inner.typ, T_DIR);
+
+        let dot = lookup_inode(ROOTINO, "/.", true).unwrap();
+        assert_eq!(dot.inum, ROOTINO);
+        assert_eq!(
+            lookup_inode(ROOTINO, "/nonexistent", true).map(|inode| inode.inum),
+            Err(Error::NotFound)
+        );
     }
 
-    let mut off = 0;
-    let mut dirent: Dirent = unsafe { MaybeUninit::zeroed().assume_init() };
-    while off < fsinode.inner.size as usize {
-        if !readi(fsinode, off, &mut dirent) {
-            panic!("dirlink() get dirent fail");
-        }
-
-        /* If there is an empty dirent, just making link with it.
-         * Otherwise we will extend the inode size(by writei)to append
-         * a new entry */
-        if dirent.inum == 0 {
-            break;
-        }
+    #[test_case]
+    fn link_and_lookup() {
+        let inum = mknod(ROOTINO, "/test", T_FILE, 0, 0).unwrap();
+        // The name should be unique under the directory
+        assert_eq!(mknod(ROOTINO, "/test", T_FILE, 0, 0), Err(Error::Exists));
+
+        let file = lookup_inode(ROOTINO, "/test", true).unwrap();
+        assert_eq!(file.inum, inum);
+        assert_eq!(file.inner.typ, T_FILE);
+        drop(file);
+        // The relative path is started from the given directory
+        let file = lookup_inode(ROOTINO, "./test", true).unwrap();
+        assert_eq!(file.inum, inum);
+        drop(file);
+        assert_eq!(inode_path(ROOTINO).as_deref(), Some("/"));
+
+        assert_eq!(unlink(ROOTINO, "/test"), Ok(()));
+    }
 
-        off += size_of::<Dirent>();
+    #[test_case]
+    fn unlink_open_file() {
+        let inum = mknod(ROOTINO, "/opened", T_FILE, 0, 0).unwrap();
+        open_file(inum);
+        open_file(inum);
+        assert_eq!(unlink(ROOTINO, "/opened"), Ok(()));
+
+        // The inode is kept until the last open file is closed
+        close_file(inum);
+        assert_eq!(find_inode(inum).inner.nlink, 0);
+        close_file(inum);
+        assert!(FS.read_inode(inum).is_free());
     }
 
-    dirent.update(inum, name);
+    #[test_case]
+    fn remove_open_directory() {
+        let dir = mkdir(ROOTINO, "/opendir").unwrap();
+        open_file(dir);
+        assert_eq!(rmdir(ROOTINO, "/opendir"), Ok(()));
 
-    if !writei(fsinode, off, &dirent) {
-        return false;
-    }
+        // What fstat sees through the open file
+        let inode = find_inode(dir);
+        assert_eq!((inode.inner.typ, inode.inner.nlink), (T_DIR, 0));
+        drop(inode);
+        assert_eq!(mkdir(dir, "sub"), Err(Error::NotFound));
 
-    true
-}
+        close_file(dir);
+        assert!(FS.read_inode(dir).is_free());
+    }
 
-// Find the corresponding inode by the path
-pub fn path_to_inode(mut path: &str) -> Option<FsInode> {
-    dbg!("Traslate path {} to inode", path);
-
-    /* FIXME: We only support to use the absolute path which
-     * starting from root now. Allow relative path in the future. */
-    let mut inode;
-    if path.chars().nth(0) == Some('/') {
-        path = &path[1..];
-        inode = find_inode(ROOTINO);
-    } else {
-        todo!("path_to_inode() not start from node");
+    #[test_case]
+    fn namespace_operations() {
+        let dir = mkdir(ROOTINO, "/nsdir").unwrap();
+        assert_eq!(mkdir(ROOTINO, "/nsdir"), Err(Error::Exists));
+        assert_eq!(rename(ROOTINO, "/nsdir", "/nsdir/sub"), Err(Error::Invalid));
+        assert_eq!(rename(dir, "../nsdir", "/nsdir2"), Ok(()));
+        assert_eq!(inode_path(dir).as_deref(), Some("/nsdir2"));
+        assert_eq!(unlink(ROOTINO, "/nsdir2"), Err(Error::IsDir));
+        assert_eq!(rmdir(ROOTINO, "/nsdir2"), Ok(()));
+        assert!(lookup_inode(ROOTINO, "/nsdir2", true).is_err());
     }
 
-    while let Some((path_head, path_tail)) = parse_path(path) {
-        /* This inode is corresponded to a directory, but we want to find
-         * a file under it. This is an invalid request. */
-        if inode.inner.typ != T_DIR {
-            return None;
-        }
+    #[test_case]
+    fn follow_symlink() {
+        let dir = mkdir(ROOTINO, "/symdir").unwrap();
+        symlink(ROOTINO, "symdir", "/symlink").unwrap();
+        assert_eq!(readlink(ROOTINO, "/symlink").as_deref(), Ok("symdir"));
+        assert_eq!(lookup_inode(ROOTINO, "/symlink", true).unwrap().inum, dir);
+        let link = lookup_inode(ROOTINO, "/symlink", false).unwrap();
+        assert_eq!(link.inner.typ, T_SYMLINK);
+        drop(link);
+
+        assert_eq!(unlink(ROOTINO, "/symlink"), Ok(()));
+        assert_eq!(rmdir(ROOTINO, "/symdir"), Ok(()));
+    }
 
-        let next = dirlookup(&inode, path_head);
-        if let Some(next) = next {
-            path = path_tail;
-            inode = next;
-            continue;
-        }
-        return None;
+    #[test_case]
+    fn new_inode_metadata() {
+        let dir = mkdir(ROOTINO, "/metadir").unwrap();
+        let inode = find_inode(dir);
+        assert_eq!(inode.inner.mode, 0o755);
+        assert_eq!(inode.inner.uid, ROOT_UID);
+        assert!(inode.inner.ctime > 0 && inode.inner.mtime >= inode.inner.ctime);
+        assert!(inode.inner.permits(1, 1, MAY_READ | MAY_EXEC));
+        assert!(!inode.inner.permits(1, 1, MAY_WRITE));
+        drop(inode);
+
+        assert_eq!(rmdir(ROOTINO, "/metadir"), Ok(()));
     }
 
-    Some(inode)
+    #[test_case]
+    fn user_buffer_io() {
+        const VADDR: u64 = 0x1000_0000;
+        let page = page::zalloc(0);
+        let mut mm = Mapping::new();
+        mm.map(Segment {
+            vaddr: VADDR,
+            paddr: page as u64,
+            len: PAGE_SIZE as u64,
+            flags: PteFlag::READ | PteFlag::WRITE | PteFlag::USER,
+        });
+
+        let mut file = find_inode(mknod(ROOTINO, "/userio", T_FILE, 0, 0).unwrap());
+        let user = unsafe { core::slice::from_raw_parts_mut(page, PAGE_SIZE) };
+        user[..5].copy_from_slice(b"hello");
+        let addr = VADDR as usize;
+        assert_eq!(writei_user(&mut file, 3, &mm, addr, 5), Some(5));
+        assert_eq!(file.inner.size, 8);
+
+        // The read is short at the end of file, and the hole is zero
+        user.fill(0xff);
+        file.inner.atime = 0;
+        assert_eq!(readi_user(&mut file, 0, &mm, addr + 1, 100), Some(8));
+        assert_eq!(&user[..10], b"\xff\0\0\0hello\xff");
+        assert!(file.inner.atime != 0);
+        assert_eq!(readi_user(&mut file, 8, &mm, addr, 100), Some(0));
+        // The buffer beyond the mapped page is not accessed
+        assert_eq!(
+            readi_user(&mut file, 0, &mm, addr + PAGE_SIZE - 2, 8),
+            Some(2)
+        );
+        assert_eq!(readi_user(&mut file, 0, &mm, addr + PAGE_SIZE, 8), None);
+
+        drop(file);
+        assert_eq!(unlink(ROOTINO, "/userio"), Ok(()));
+        drop(mm);
+        page::free(page);
+    }
 }
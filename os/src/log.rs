@@ -0,0 +1,310 @@
+/* The leveled kernel logging. A message is dropped in compile time if
+ * its level is above MAX_LEVEL which is selected by cargo feature, and
+ * then in runtime if it is above the threshold of the module it comes
+ * from. The messages that pass are recorded in the log ring, which can
+ * be read back by the syslog syscall, and those within the console level
+ * are also printed to console. */
+use crate::cpu;
+use crate::lock::Locked;
+use crate::time::{monotonic_ns, NSEC_PER_SEC, NSEC_PER_USEC};
+use crate::utils::ringbuf::RingBuf;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Error, Write};
+use lazy_static::lazy_static;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Level::Error => "\x1b[1;31m",
+            Level::Warn => "\x1b[1;33m",
+            Level::Info => "\x1b[1;94m",
+            Level::Debug => "\x1b[0;32m",
+            Level::Trace => "\x1b[0;90m",
+        }
+    }
+}
+
+// The highest level to be compiled, which is selected by cargo feature
+#[cfg(feature = "max_level_error")]
+pub const MAX_LEVEL: Level = Level::Error;
+#[cfg(all(feature = "max_level_warn", not(feature = "max_level_error")))]
+pub const MAX_LEVEL: Level = Level::Warn;
+#[cfg(all(
+    feature = "max_level_info",
+    not(any(feature = "max_level_error", feature = "max_level_warn"))
+))]
+pub const MAX_LEVEL: Level = Level::Info;
+#[cfg(all(
+    feature = "max_level_debug",
+    not(any(
+        feature = "max_level_error",
+        feature = "max_level_warn",
+        feature = "max_level_info"
+    ))
+))]
+pub const MAX_LEVEL: Level = Level::Debug;
+#[cfg(not(any(
+    feature = "max_level_error",
+    feature = "max_level_warn",
+    feature = "max_level_info",
+    feature = "max_level_debug"
+)))]
+pub const MAX_LEVEL: Level = Level::Trace;
+
+// The threshold for the module without its own one
+const DEFAULT_LEVEL: Level = Level::Info;
+// The highest level printed to console among the recorded messages
+const CONSOLE_LEVEL: Level = Level::Trace;
+// The maximum number of modules which have their own threshold
+const MAX_FILTERS: usize = 8;
+const LOG_BUF_SIZE: usize = 4096;
+
+struct Logger {
+    default: Level,
+    console: Level,
+    filters: Vec<(String, Level)>,
+    ring: RingBuf<u8, LOG_BUF_SIZE>,
+}
+
+impl Logger {
+    fn new() -> Self {
+        Logger {
+            default: DEFAULT_LEVEL,
+            console: CONSOLE_LEVEL,
+            filters: Vec::with_capacity(MAX_FILTERS),
+            ring: RingBuf::new(),
+        }
+    }
+
+    /* Find the threshold of the module. The longest matched path wins,
+     * so "os::fs::inode" can be set apart from "os::fs". */
+    fn threshold(&self, module: &str) -> Level {
+        let mut level = self.default;
+        let mut matched = 0;
+        for (path, l) in &self.filters {
+            let is_match = module == *path
+                || (module.starts_with(path) && module[path.len()..].starts_with("::"));
+            if is_match && path.len() > matched {
+                level = *l;
+                matched = path.len();
+            }
+        }
+        level
+    }
+
+    fn set_level(&mut self, module: &str, level: Level) -> bool {
+        if let Some(filter) = self.filters.iter_mut().find(|(path, _)| path == module) {
+            filter.1 = level;
+            return true;
+        }
+
+        if self.filters.len() == MAX_FILTERS {
+            return false;
+        }
+        self.filters.push((String::from(module), level));
+        true
+    }
+}
+
+impl Write for Logger {
+    fn write_str(&mut self, out: &str) -> Result<(), Error> {
+        for c in out.bytes() {
+            self.ring.push_overwrite(c);
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref LOGGER: Locked<Logger> = Locked::new(Logger::new());
+}
+
+/* This is called by the log macros. The interrupt is disabled when
+ * holding the lock, since we also log in interrupt handler. */
+pub fn log(level: Level, module: &'static str, args: fmt::Arguments) {
+    let intr = cpu::intr_save();
+    let mut logger = LOGGER.lock();
+    if level > logger.threshold(module) {
+        drop(logger);
+        cpu::intr_restore(intr);
+        return;
+    }
+
+    let ns = monotonic_ns();
+    let _ = writeln!(
+        logger,
+        "[{:5}.{:06}] {}: {}: {}",
+        ns / NSEC_PER_SEC,
+        (ns % NSEC_PER_SEC) / NSEC_PER_USEC,
+        level.name(),
+        module,
+        args
+    );
+    let print = level <= logger.console;
+    drop(logger);
+    cpu::intr_restore(intr);
+
+    if print {
+        crate::console::print(format_args!("{}{}\x1b[0m\n", level.color(), args));
+    }
+}
+
+/* Set the runtime threshold of the module and its submodules, which is
+ * identified by the path like "os::fs". Return false if there's no room
+ * for a new module. */
+pub fn set_level(module: &str, level: Level) -> bool {
+    let intr = cpu::intr_save();
+    let ret = LOGGER.lock().set_level(module, level);
+    cpu::intr_restore(intr);
+    ret
+}
+
+// Set the threshold for the modules without their own one
+pub fn set_default_level(level: Level) {
+    let intr = cpu::intr_save();
+    LOGGER.lock().default = level;
+    cpu::intr_restore(intr);
+}
+
+/* Set the highest level of messages printed to console. The messages
+ * above it are still recorded in the log ring. */
+pub fn set_console_level(level: Level) {
+    let intr = cpu::intr_save();
+    LOGGER.lock().console = level;
+    cpu::intr_restore(intr);
+}
+
+/* Copy the latest messages in the log ring to the buffer without
+ * consuming them. Return the number of bytes copied. */
+pub fn read_all(buf: &mut [u8]) -> usize {
+    let intr = cpu::intr_save();
+    let logger = LOGGER.lock();
+    let len = logger.ring.len();
+    let n = buf.len().min(len);
+    for (i, b) in buf[..n].iter_mut().enumerate() {
+        *b = logger.ring.get(len - n + i).unwrap();
+    }
+    drop(logger);
+    cpu::intr_restore(intr);
+    n
+}
+
+// Consume the oldest messages in the log ring
+pub fn read(buf: &mut [u8]) -> usize {
+    let intr = cpu::intr_save();
+    let mut logger = LOGGER.lock();
+    let mut n = 0;
+    while n < buf.len() {
+        match logger.ring.pop() {
+            Some(c) => buf[n] = c,
+            None => break,
+        }
+        n += 1;
+    }
+    drop(logger);
+    cpu::intr_restore(intr);
+    n
+}
+
+pub fn clear() {
+    let intr = cpu::intr_save();
+    LOGGER.lock().ring.clear();
+    cpu::intr_restore(intr);
+}
+
+// The number of bytes which are available in the log ring
+pub fn unread_size() -> usize {
+    let intr = cpu::intr_save();
+    let n = LOGGER.lock().ring.len();
+    cpu::intr_restore(intr);
+    n
+}
+
+pub const fn buf_size() -> usize {
+    LOG_BUF_SIZE
+}
+
+macro_rules! log {
+    ($level: expr, $fmt: literal $(, $($arg: tt)+)?) => {{
+        /* The comparison between constants is resolved by compiler, so
+         * the message above MAX_LEVEL costs nothing. */
+        if $level <= $crate::log::MAX_LEVEL {
+            $crate::log::log($level, module_path!(), format_args!($fmt $(, $($arg)+)?));
+        }
+    }};
+}
+
+macro_rules! error {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        log!($crate::log::Level::Error, $fmt $(, $($arg)+)?)
+    };
+}
+
+macro_rules! warning {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        log!($crate::log::Level::Warn, $fmt $(, $($arg)+)?)
+    };
+}
+
+macro_rules! info {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        log!($crate::log::Level::Info, $fmt $(, $($arg)+)?)
+    };
+}
+
+macro_rules! dbg {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        log!($crate::log::Level::Debug, $fmt $(, $($arg)+)?)
+    };
+}
+
+macro_rules! trace {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        log!($crate::log::Level::Trace, $fmt $(, $($arg)+)?)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn module_threshold() {
+        let mut logger = Logger::new();
+        assert!(logger.set_level("os::fs", Level::Debug));
+        assert!(logger.set_level("os::fs::inode", Level::Error));
+        assert!(logger.threshold("os::fs") == Level::Debug);
+        assert!(logger.threshold("os::fs::file") == Level::Debug);
+        assert!(logger.threshold("os::fs::inode") == Level::Error);
+        // Only the whole element of path is matched
+        assert!(logger.threshold("os::fsck") == DEFAULT_LEVEL);
+
+        // The existing module is updated without taking a new slot
+        assert!(logger.set_level("os::fs", Level::Warn));
+        assert!(logger.threshold("os::fs") == Level::Warn);
+        for i in 2..MAX_FILTERS {
+            assert!(logger.set_level(&alloc::format!("os::m{}", i), Level::Warn));
+        }
+        assert!(!logger.set_level("os::full", Level::Warn));
+    }
+}
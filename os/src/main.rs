@@ -7,6 +7,9 @@
 #![feature(panic_info_message, alloc_error_handler)]
 #![feature(const_mut_refs)]
 #![feature(fn_align)]
+#![feature(custom_test_frameworks)]
+#![test_runner(crate::test::runner)]
+#![reexport_test_harness_main = "test_main"]
 
 extern crate alloc;
 
@@ -26,6 +29,10 @@ mod console;
 #[macro_use]
 mod macros;
 
+#[macro_use]
+mod log;
+
+mod backtrace;
 mod bio;
 mod clint;
 mod config;
@@ -35,9 +42,15 @@ mod lock;
 mod mm;
 mod panic;
 mod plic;
+mod power;
+mod rtc;
 mod sched;
 mod syscall;
+#[cfg(test)]
+mod test;
+mod time;
 mod trap;
+mod tty;
 mod uart;
 mod utils;
 mod virtio;
@@ -57,8 +70,14 @@ pub extern "C" fn kmain() -> ! {
     print!("Welcome to AmiRVOS world!\n");
 
     plic::init();
+    rtc::init();
+    time::init();
     virtio::blk::init();
     fs::init();
+
+    #[cfg(test)]
+    test_main();
+
     sched::init();
 
     /* Start the timer tick, the scheduler will then start on
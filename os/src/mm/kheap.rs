@@ -19,3 +19,36 @@ pub fn init() {
             .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+
+    #[test_case]
+    fn box_alloc() {
+        let a = Box::new(41);
+        let b = Box::new(13);
+        assert_eq!(*a + *b, 54);
+    }
+
+    #[test_case]
+    fn vec_grow() {
+        let n = 1000;
+        let mut v = Vec::new();
+        for i in 0..n {
+            v.push(i);
+        }
+        assert_eq!(v.iter().sum::<usize>(), (n - 1) * n / 2);
+    }
+
+    #[test_case]
+    fn reuse_freed_memory() {
+        /* The total allocation is far beyond the heap size, which only
+         * succeeds if the freed memory is reused. */
+        for i in 0..super::KERNEL_HEAP_SIZE / 1024 * 4 {
+            let x = Box::new([i as u8; 1024]);
+            assert_eq!(x[1023], i as u8);
+        }
+    }
+}
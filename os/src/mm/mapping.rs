@@ -4,6 +4,7 @@ use crate::mm::page;
 use alloc::{vec, vec::Vec};
 use bitflags::*;
 use core::ops::{Index, IndexMut};
+use core::slice;
 use lazy_static::lazy_static;
 use riscv::register::satp;
 
@@ -12,7 +13,7 @@ lazy_static! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
     pub struct PteFlag: u8 {
     const VALID = 1 << 0;
     const READ = 1 << 1;
@@ -74,6 +75,10 @@ impl Pte {
             || (self.0 & PteFlag::EXECUTE.bits() as u64) != 0)
     }
     #[inline]
+    fn flags(&self) -> PteFlag {
+        PteFlag::from_bits_truncate(self.0 as u8)
+    }
+    #[inline]
     fn page_num(&self) -> u64 {
         (self.0 & 0x003f_ffff_ffff_fc00) >> 10
     }
@@ -86,6 +91,15 @@ pub struct Segment {
     pub flags: PteFlag,
 }
 
+// The reasons why a string can't be copied from user memory
+#[derive(Debug, PartialEq, Eq)]
+pub enum StrError {
+    // The string is not readable by user
+    Fault,
+    // The string is not terminated within the maximum length
+    TooLong,
+}
+
 pub struct Mapping {
     page_tables: Vec<PageTable>,
     root_ppn: u64,
@@ -163,9 +177,9 @@ impl Mapping {
         );
     }
 
-    fn walk(&self, vaddr: u64) -> Option<u64> {
-        let vaddr = align_down!(vaddr, PAGE_SIZE as u64);
-
+    /* Translate the virtual address to the physical address, and return
+     * it with the flags of the leaf entry. Return None if it is not mapped. */
+    fn walk(&self, vaddr: u64) -> Option<(u64, PteFlag)> {
         let vpn = [
             (vaddr >> 12) & 0x1ff,
             (vaddr >> 21) & 0x1ff,
@@ -189,52 +203,134 @@ impl Mapping {
                 break;
             }
         }
+
+        // The last level entry should be a valid leaf
+        if !next_entry.is_valid() || next_entry.has_next_level() {
+            return None;
+        }
+
         let offset = vaddr & ((1 << offset_length) - 1);
-        return Some(next_entry.page_num() << 12 + offset as u64);
+        Some(((next_entry.page_num() << 12) + offset, next_entry.flags()))
     }
 
+    /* Translate the user address to the physical one. The page should be
+     * accessible in user mode with `perm`, otherwise it is a fault and
+     * None is returned. The address beyond MAX_VA is a fault too, as the
+     * page table only translates the lower bits of it. */
+    fn user_pa(&self, addr: usize, perm: PteFlag) -> Option<u64> {
+        if addr >= MAX_VA {
+            return None;
+        }
+        let (pa, flags) = self.walk(addr as u64)?;
+        if !flags.contains(PteFlag::USER | PteFlag::READ | perm) {
+            return None;
+        }
+
+        Some(pa)
+    }
+
+    /* Get the user memory from `addr` to the end of its page, which is at
+     * most `len` bytes, so the kernel can read it in place. Return None
+     * if it is not readable by user.
+     *
+     * FIXME: The memory is not owned by the mapping, so nothing prevents
+     * it from being changed by a slice from user_slice_mut(). */
+    pub fn user_slice(&self, addr: usize, len: usize) -> Option<&[u8]> {
+        let pa = self.user_pa(addr, PteFlag::empty())?;
+        let n = (PAGE_SIZE - addr % PAGE_SIZE).min(len);
+        Some(unsafe { slice::from_raw_parts(pa as *const u8, n) })
+    }
+
+    /* Like user_slice(), but the kernel can also write the memory in place.
+     * Return None if it is not writable by user.
+     *
+     * FIXME: The memory is not owned by the mapping, so nothing prevents
+     * two slices of the same user page from being alive together. */
+    pub fn user_slice_mut(&self, addr: usize, len: usize) -> Option<&mut [u8]> {
+        let pa = self.user_pa(addr, PteFlag::WRITE)?;
+        let n = (PAGE_SIZE - addr % PAGE_SIZE).min(len);
+        Some(unsafe { slice::from_raw_parts_mut(pa as *mut u8, n) })
+    }
+
+    /* Copy `buf.len()` bytes from the user memory at `addr`. Return false
+     * if any part of it can't be read by user. */
     pub fn copy_from_user(&self, addr: usize, buf: &mut [u8]) -> bool {
-        let len = buf.len();
-        let mut addr = addr;
         let mut total = 0;
-        let mut success = false;
+        while total < buf.len() {
+            let addr = addr.checked_add(total);
+            let src = match addr.and_then(|addr| self.user_slice(addr, buf.len() - total)) {
+                Some(src) => src,
+                None => return false,
+            };
+            buf[total..total + src.len()].copy_from_slice(src);
+            total += src.len();
+        }
 
-        while !success && total < len {
-            let va = align_down!(addr, PAGE_SIZE);
-            let pa = self.walk(va as u64);
-            // Unable to find the corresponding physical address
-            if pa.is_none() {
-                break;
-            }
-            let pa = pa.unwrap() as usize;
-            let mut ptr = (pa + (addr - va)) as *const u8;
-            let mut n = (PAGE_SIZE - (addr - va)).min(len - total);
-            while n > 0 {
-                buf[total] = unsafe { *ptr };
-
-                if unsafe { *ptr } == 0 {
-                    success = true;
-                    break;
-                }
+        true
+    }
 
-                n -= 1;
-                total += 1;
-                unsafe {
-                    ptr = ptr.add(1);
+    /* Copy the buffer to the user memory at `addr`. Return false if any
+     * part of it can't be written by user. */
+    pub fn copy_to_user(&self, addr: usize, buf: &[u8]) -> bool {
+        let mut total = 0;
+        while total < buf.len() {
+            let addr = addr.checked_add(total);
+            let dst = match addr.and_then(|addr| self.user_slice_mut(addr, buf.len() - total)) {
+                Some(dst) => dst,
+                None => return false,
+            };
+            let n = dst.len();
+            dst.copy_from_slice(&buf[total..total + n]);
+            total += n;
+        }
+
+        true
+    }
+
+    /* Copy the nul-terminated string from the user memory at `addr`, and
+     * return it without the trailing '\0'. The string including '\0'
+     * should be at most `max` bytes. */
+    pub fn copy_str_from_user(&self, addr: usize, max: usize) -> Result<Vec<u8>, StrError> {
+        let mut buf = Vec::new();
+        while buf.len() < max {
+            let addr = addr.checked_add(buf.len());
+            let src = match addr.and_then(|addr| self.user_slice(addr, max - buf.len())) {
+                Some(src) => src,
+                None => return Err(StrError::Fault),
+            };
+            match src.iter().position(|&c| c == 0) {
+                Some(n) => {
+                    buf.extend_from_slice(&src[..n]);
+                    return Ok(buf);
                 }
+                None => buf.extend_from_slice(src),
             }
-
-            addr = va + PAGE_SIZE;
         }
 
-        success
+        Err(StrError::TooLong)
     }
 }
 
+// Free the page table and all the next level page tables under it
+fn free_table(table: &PageTable, level: usize) {
+    if level > 0 {
+        for idx in 0..512 {
+            let entry = &table.entries[idx];
+            if entry.is_valid() && entry.has_next_level() {
+                free_table(&entry.get_next_table(), level - 1);
+            }
+        }
+    }
+    page::free(table.entries.0 as *mut u8);
+}
+
 impl Drop for Mapping {
-    /* TODO: Reclaim memory for the page allocation */
     fn drop(&mut self) {
-        todo!("reclaimation for map")
+        /* Only the page tables are owned by the mapping. The mapped pages
+         * should be reclaimed by their owner. */
+        for table in &self.page_tables {
+            free_table(table, 2);
+        }
     }
 }
 
@@ -300,6 +396,20 @@ pub fn init() {
             flags: PteFlag::READ | PteFlag::WRITE,
         });
 
+        MAPPING.lock().map(Segment {
+            vaddr: FINISHER_BASE as u64,
+            paddr: FINISHER_BASE as u64,
+            len: FINISHER_SIZE as u64,
+            flags: PteFlag::READ | PteFlag::WRITE,
+        });
+
+        MAPPING.lock().map(Segment {
+            vaddr: RTC_BASE as u64,
+            paddr: RTC_BASE as u64,
+            len: RTC_SIZE as u64,
+            flags: PteFlag::READ | PteFlag::WRITE,
+        });
+
         MAPPING.lock().map(Segment {
             vaddr: PLIC_BASE as u64,
             paddr: PLIC_BASE as u64,
@@ -326,13 +436,130 @@ pub fn init() {
     MAPPING.lock().activate();
 }
 
-pub fn test() {
-    /* simply check if we did linear map the address space */
-    let vaddr = (DRAM_BASE + 0x2000) as u64;
-    match MAPPING.lock().walk(vaddr) {
-        None => panic!("walking page table of vaddr {:X} failed!\n", vaddr),
-        Some(paddr) => {
-            assert_eq!(vaddr, paddr);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn kernel_linear_map() {
+        let vaddr = (DRAM_BASE + 0x2000) as u64;
+        assert_eq!(MAPPING.lock().walk(vaddr).map(|(pa, _)| pa), Some(vaddr));
+        // The offset in page is kept after translation
+        assert_eq!(
+            MAPPING.lock().walk(vaddr + 0x123).map(|(pa, _)| pa),
+            Some(vaddr + 0x123)
+        );
+    }
+
+    #[test_case]
+    fn map_and_walk() {
+        let mut mapping = Mapping::new();
+        let page = page::zalloc(0);
+        let vaddr = TASK_START_ADDR as u64;
+
+        assert_eq!(mapping.walk(vaddr), None);
+        mapping.map(Segment {
+            vaddr,
+            paddr: page as u64,
+            len: PAGE_SIZE as u64,
+            flags: PteFlag::READ | PteFlag::WRITE | PteFlag::USER,
+        });
+        assert_eq!(
+            mapping.walk(vaddr + 8),
+            Some((
+                page as u64 + 8,
+                PteFlag::VALID | PteFlag::READ | PteFlag::WRITE | PteFlag::USER
+            ))
+        );
+        // The neighbor page shares the page tables but is not mapped
+        assert_eq!(mapping.walk(vaddr + PAGE_SIZE as u64), None);
+
+        drop(mapping);
+        page::free(page);
+    }
+
+    #[test_case]
+    fn copy_user_roundtrip() {
+        let mut mapping = Mapping::new();
+        let page = page::zalloc(0);
+        let vaddr = TASK_START_ADDR;
+        mapping.map(Segment {
+            vaddr: vaddr as u64,
+            paddr: page as u64,
+            len: PAGE_SIZE as u64,
+            flags: PteFlag::READ | PteFlag::WRITE | PteFlag::USER,
+        });
+
+        let src = [1u8, 2, 3, 4];
+        let mut dst = [0u8; 4];
+        assert!(mapping.copy_to_user(vaddr + 8, &src));
+        assert!(mapping.copy_from_user(vaddr + 8, &mut dst));
+        assert_eq!(src, dst);
+
+        // The copy fails across the end of mapped memory
+        assert!(!mapping.copy_to_user(vaddr + PAGE_SIZE - 2, &src));
+        assert!(!mapping.copy_from_user(vaddr + PAGE_SIZE - 2, &mut dst));
+
+        drop(mapping);
+        page::free(page);
+    }
+
+    #[test_case]
+    fn copy_user_permission() {
+        let mut mapping = Mapping::new();
+        let pages = [page::zalloc(0), page::zalloc(0)];
+        let vaddr = TASK_START_ADDR;
+        mapping.map(Segment {
+            vaddr: vaddr as u64,
+            paddr: pages[0] as u64,
+            len: PAGE_SIZE as u64,
+            flags: PteFlag::READ | PteFlag::USER,
+        });
+        mapping.map(Segment {
+            vaddr: (vaddr + PAGE_SIZE) as u64,
+            paddr: pages[1] as u64,
+            len: PAGE_SIZE as u64,
+            flags: PteFlag::READ | PteFlag::WRITE,
+        });
+
+        let mut buf = [0u8; 4];
+        assert!(mapping.copy_from_user(vaddr, &mut buf));
+        // The read-only page can't be written
+        assert!(!mapping.copy_to_user(vaddr, &buf));
+        // The kernel page can't be accessed on behalf of user
+        assert!(!mapping.copy_from_user(vaddr + PAGE_SIZE, &mut buf));
+        // The high bits of address are not ignored
+        assert!(!mapping.copy_from_user(vaddr | (1 << 40), &mut buf));
+        assert!(!mapping.copy_from_user(usize::MAX - 1, &mut buf));
+
+        drop(mapping);
+        page::free(pages[0]);
+        page::free(pages[1]);
+    }
+
+    #[test_case]
+    fn copy_str_from_user() {
+        let mut mapping = Mapping::new();
+        let page = page::zalloc(0);
+        let vaddr = TASK_START_ADDR;
+        mapping.map(Segment {
+            vaddr: vaddr as u64,
+            paddr: page as u64,
+            len: PAGE_SIZE as u64,
+            flags: PteFlag::READ | PteFlag::WRITE | PteFlag::USER,
+        });
+
+        assert!(mapping.copy_to_user(vaddr, b"hello\0"));
+        assert_eq!(mapping.copy_str_from_user(vaddr, 6).unwrap(), b"hello");
+        assert_eq!(mapping.copy_str_from_user(vaddr, 5), Err(StrError::TooLong));
+        // The string is not terminated before the end of mapped memory
+        assert!(mapping.copy_to_user(vaddr + PAGE_SIZE - 2, b"hi"));
+        assert_eq!(
+            mapping.copy_str_from_user(vaddr + PAGE_SIZE - 2, 16),
+            Err(StrError::Fault)
+        );
+
+        drop(mapping);
+        page::free(page);
     }
 }
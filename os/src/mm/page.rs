@@ -125,20 +125,40 @@ pub fn free(ptr: *mut u8) {
     }
 }
 
-pub fn test() {
-    // test the page allocation behavior
-    let a = alloc(0);
-    let b = alloc(0);
-    let c = alloc(0);
-
-    free(a);
-    let d = alloc(1);
-    free(b);
-    let e = alloc(1);
-
-    assert_eq!(a, e);
-
-    free(c);
-    free(d);
-    free(e);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn alloc_reuse_freed() {
+        let a = alloc(0);
+        let b = alloc(0);
+        let c = alloc(0);
+
+        // The two pages freed should be merged for the larger allocation
+        free(a);
+        let d = alloc(1);
+        free(b);
+        let e = alloc(1);
+
+        assert_eq!(a, e);
+
+        free(c);
+        free(d);
+        free(e);
+    }
+
+    #[test_case]
+    fn zalloc_zeroed() {
+        let p = alloc(0);
+        unsafe {
+            p.write_bytes(0xff, PAGE_SIZE);
+        }
+        free(p);
+
+        let p = zalloc(0);
+        let page = unsafe { core::slice::from_raw_parts(p, PAGE_SIZE) };
+        assert!(page.iter().all(|b| *b == 0));
+        free(p);
+    }
 }
@@ -1,11 +1,83 @@
+use crate::backtrace::{self, Symbol};
+use crate::lock::Locked;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+// The context of the trap which is going to panic
+pub struct TrapInfo {
+    pub regs: [usize; 32],
+    pub epc: usize,
+    pub cause: usize,
+    pub tval: usize,
+}
+
+static TRAP_INFO: Locked<Option<TrapInfo>> = Locked::new(None);
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/* Record the context of trap before the trap handler panics, so the
+ * registers can be dumped by the panic handler. */
+pub fn set_trap_info(info: TrapInfo) {
+    if let Some(mut trap_info) = TRAP_INFO.try_lock() {
+        *trap_info = Some(info);
+    }
+}
+
+fn dump_trap_info() {
+    let trap_info = match TRAP_INFO.try_lock() {
+        Some(trap_info) => trap_info,
+        None => return,
+    };
+
+    if let Some(info) = trap_info.as_ref() {
+        panic_println!(
+            "trap: cause={:#x} tval={:#x} epc={}",
+            info.cause,
+            info.tval,
+            Symbol(info.epc)
+        );
+        for (i, chunk) in info.regs.chunks(4).enumerate() {
+            panic_println!(
+                "  {:>4}: {:016x} {:>4}: {:016x} {:>4}: {:016x} {:>4}: {:016x}",
+                REG_NAMES[i * 4],
+                chunk[0],
+                REG_NAMES[i * 4 + 1],
+                chunk[1],
+                REG_NAMES[i * 4 + 2],
+                chunk[2],
+                REG_NAMES[i * 4 + 3],
+                chunk[3]
+            );
+        }
+    }
+}
 
 #[panic_handler]
 fn panic_handler(info: &PanicInfo) -> ! {
+    #[cfg(test)]
+    panic_println!("\x1b[1;31mFAILED\x1b[0m");
+
     if let Some(s) = info.payload().downcast_ref::<&str>() {
-        println!("\x1b[1;31mpanic: '{}'\x1b[0m", s);
+        panic_println!("\x1b[1;31mpanic: '{}'\x1b[0m", s);
     } else {
-        println!("\x1b[1;31mpanic: '{}'\x1b[0m", info.message().unwrap());
+        panic_println!("\x1b[1;31mpanic: '{}'\x1b[0m", info.message().unwrap());
     }
+
+    // Don't try to unwind again if we panic in the panic handler
+    if !PANICKING.swap(true, Ordering::Relaxed) {
+        dump_trap_info();
+        backtrace::backtrace();
+    }
+
+    // Let the automated run know the failure instead of hanging
+    #[cfg(any(test, feature = "panic_poweroff"))]
+    crate::power::poweroff(1);
+
+    #[cfg(not(any(test, feature = "panic_poweroff")))]
     loop {}
 }
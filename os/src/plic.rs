@@ -1,16 +1,19 @@
 /* [PLIC Reference](https://github.com/riscv/riscv-plic-spec/blob/master/riscv-plic.adoc)
  * [PLIC memory map of Qemu on xv6-riscv](https://github.com/mit-pdos/xv6-riscv/blob/riscv/kernel/memlayout.h)
  */
-use crate::uart;
 use crate::virtio::blk;
+use crate::{rtc, uart};
 
 const VIRTIO0_IRQ: u32 = 1;
 const UART0_IRQ: u32 = 10;
+const RTC_IRQ: u32 = 11;
 
 // Interrupt source VIRTIO0_IRQ(=1) priority
 mmap_reg!(plic_pri_virtio0, 0xc00_0000 + 4 * 1, u32);
 // Interrupt source UART0_IRQ(=10) priority
 mmap_reg!(plic_pri_uart0, 0xc00_0000 + 4 * 10, u32);
+// Interrupt source RTC_IRQ(=11) priority
+mmap_reg!(plic_pri_rtc, 0xc00_0000 + 4 * 11, u32);
 // Enable bits for sources 0-31 on context 1(CPU0 / S mode)
 mmap_reg!(plic_senable, 0xc00_0000 + 0x2080, u32);
 // Priority threshold for context 1
@@ -22,9 +25,10 @@ pub fn init() {
     // set IRQs priority to non-zero
     plic_pri_virtio0::write(1);
     plic_pri_uart0::write(1);
+    plic_pri_rtc::write(1);
 
     // enable IRQs for hart 0 in S mode
-    plic_senable::write(1 << VIRTIO0_IRQ | 1 << UART0_IRQ);
+    plic_senable::write(1 << VIRTIO0_IRQ | 1 << UART0_IRQ | 1 << RTC_IRQ);
 
     // set priority threshold to 0 for hart 0 in S mode
     plic_sthreshold::write(0);
@@ -39,6 +43,7 @@ pub fn irq_handler() {
     match irq {
         VIRTIO0_IRQ => blk::irq_handler(),
         UART0_IRQ => uart::irq_handler(),
+        RTC_IRQ => rtc::irq_handler(),
         _ => panic!("Unknown external interrupt: {}", irq),
     }
 
@@ -0,0 +1,31 @@
+/* The power management by the test finisher of QEMU virt machine
+ * (sifive_test), which can power off QEMU with an exit code or reset it. */
+use crate::uart::uart_flush_sync;
+
+mmap_reg!(finisher, 0x10_0000, u32);
+
+const FINISHER_FAIL: u32 = 0x3333;
+const FINISHER_PASS: u32 = 0x5555;
+const FINISHER_RESET: u32 = 0x7777;
+
+/* Power off the machine, QEMU exits with the given code. Note that only
+ * the lower 16 bits of the code is kept by the device. */
+pub fn poweroff(code: u16) -> ! {
+    // Don't lose the messages which are still buffered
+    uart_flush_sync();
+
+    if code == 0 {
+        finisher::write(FINISHER_PASS);
+    } else {
+        finisher::write((code as u32) << 16 | FINISHER_FAIL);
+    }
+
+    // The machine should be stopped before we are here
+    loop {}
+}
+
+pub fn reboot() -> ! {
+    uart_flush_sync();
+    finisher::write(FINISHER_RESET);
+    loop {}
+}
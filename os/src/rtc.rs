@@ -0,0 +1,55 @@
+/* [Goldfish RTC Reference](https://android.googlesource.com/platform/external/qemu/+/master/docs/GOLDFISH-VIRTUAL-HARDWARE.TXT)
+ * The RTC gives the nanoseconds since epoch, and it can raise an
+ * interrupt when the time reaches the programmed alarm. */
+use crate::lock::Locked;
+
+// Low 32 bits of the current time, reading it latches TIME_HIGH
+mmap_reg!(rtc_time_low, 0x10_1000 + 0x00, u32);
+// High 32 bits of the current time
+mmap_reg!(rtc_time_high, 0x10_1000 + 0x04, u32);
+// Low 32 bits of the alarm, writing it arms the alarm
+mmap_reg!(rtc_alarm_low, 0x10_1000 + 0x08, u32);
+// High 32 bits of the alarm
+mmap_reg!(rtc_alarm_high, 0x10_1000 + 0x0c, u32);
+// Write 1 to enable the alarm interrupt
+mmap_reg!(rtc_irq_enabled, 0x10_1000 + 0x10, u32);
+// Write to disarm the pending alarm
+mmap_reg!(rtc_clear_alarm, 0x10_1000 + 0x14, u32);
+// Write to acknowledge the alarm interrupt
+mmap_reg!(rtc_clear_interrupt, 0x10_1000 + 0x1c, u32);
+
+// The callback to be invoked when the alarm fires
+static ALARM_HANDLER: Locked<Option<fn()>> = Locked::new(None);
+
+// Read the nanoseconds since epoch
+pub fn read_time() -> u64 {
+    let low = rtc_time_low::read() as u64;
+    let high = rtc_time_high::read() as u64;
+    high << 32 | low
+}
+
+/* Raise an interrupt when the realtime reaches the given nanoseconds
+ * since epoch. Any alarm which is armed before will be replaced. */
+pub fn set_alarm(ns: u64, handler: fn()) {
+    *ALARM_HANDLER.lock() = Some(handler);
+
+    rtc_irq_enabled::write(1);
+    rtc_alarm_high::write((ns >> 32) as u32);
+    rtc_alarm_low::write(ns as u32);
+}
+
+pub fn init() {
+    rtc_irq_enabled::write(0);
+    rtc_clear_alarm::write(1);
+    rtc_clear_interrupt::write(1);
+}
+
+pub fn irq_handler() {
+    rtc_clear_interrupt::write(1);
+
+    // The alarm is one-shot, so the handler is also taken after firing
+    let handler = ALARM_HANDLER.lock().take();
+    if let Some(handler) = handler {
+        handler();
+    }
+}
@@ -10,6 +10,14 @@ pub struct TrapFrame {
 }
 
 impl TrapFrame {
+    pub fn set_ra(&mut self, val: usize) {
+        self.regs[1] = val;
+    }
+
+    pub fn get_sp(&self) -> usize {
+        self.regs[2]
+    }
+
     pub fn set_sp(&mut self, val: usize) {
         self.regs[2] = val;
     }
@@ -1,23 +1,24 @@
 use core::mem::MaybeUninit;
 
-use crate::cpu;
+use crate::config::CLOCK_FREQ;
 use crate::lock::Locked;
 use crate::sched::context::TaskContext;
 use crate::sched::scheduler::Scheduler;
-use crate::sched::task::Task;
+use crate::sched::task::{Task, TaskId, TaskState};
 use crate::sched::user::userinit;
+use crate::{clint, cpu, fs, tty};
 use lazy_static::lazy_static;
 
 use self::context::TrapFrame;
 
 mod context;
 mod scheduler;
+pub mod signal;
 mod task;
 mod user;
 
 extern "C" {
     fn switch_to(prev: *mut TaskContext, cur: *mut TaskContext);
-    fn delay(count: usize);
 }
 
 lazy_static! {
@@ -34,9 +35,31 @@ pub extern "C" fn initd() {
      * make the init task alive as long as the OS running. */
     loop {
         println!("initd started");
-        unsafe {
-            delay(300000000);
+        sleep_ms(3000);
+    }
+}
+
+pub extern "C" fn idle() {
+    loop {
+        /* Check the run queue with interrupt disabled. If a task is woken
+         * up by interrupt after the check, the pending interrupt still
+         * wakes up wfi, and it is taken once interrupt is enabled. */
+        cpu::intr_off();
+        let scheduler = SCHEDULER.lock();
+        let runnable = scheduler.has_runnable(clint::get_time());
+        let next_wakeup = scheduler.next_wakeup();
+        drop(scheduler);
+
+        if runnable {
+            // The task woken up by interrupt is waiting for us to yield
+            do_sched();
+        } else {
+            /* Stop the periodic tick before idle, so the CPU is only woken
+             * up by the sleeping task which is expired or other interrupts. */
+            clint::tick_stop(next_wakeup);
+            cpu::wfi();
         }
+        cpu::intr_on();
     }
 }
 
@@ -48,13 +71,21 @@ pub extern "C" fn exit() {
 }
 
 pub fn init() {
+    SCHEDULER.lock().set_idle(idle);
     SCHEDULER.lock().kspawn(initd);
     SCHEDULER.lock().kspawn(exit);
-    SCHEDULER.lock().uspawn(userinit);
+    let init = SCHEDULER.lock().uspawn(userinit);
+    // The first user task is the foreground process group of console
+    tty::set_fg_pgrp(init.0);
 }
 
 pub fn current() -> *mut Task {
+    /* The scheduler lock may be required in interrupt handler, so we
+     * should disable interrupt when holding it to avoid deadlock. */
+    let intr = cpu::intr_save();
     let cur = SCHEDULER.lock().current();
+    cpu::intr_restore(intr);
+
     assert!(!cur.is_null());
     cur
 }
@@ -68,18 +99,30 @@ pub fn current_frame() -> *mut TrapFrame {
 
 pub fn scheduler() {
     loop {
-        /* Since scheduler could be executed after timer interrupt, we
-         * need to avoid deadlock by enabling the interrupt again */
-        cpu::intr_on();
+        /* The scheduler lock may be required in interrupt handler, so we
+         * should disable interrupt when holding it to avoid deadlock. */
+        cpu::intr_off();
 
         let mut scheduler_lock = SCHEDULER.try_lock();
         let cur;
         if let Some(ref mut scheduler) = scheduler_lock {
-            if let Some(task) = scheduler.pick_next() {
+            /* We are on the kernel stack now, so it is safe to
+             * release the dead tasks. */
+            scheduler.reap();
+
+            let next;
+            if let Some(task) = scheduler.pick_next(clint::get_time()) {
                 cur = task.task_context();
+                next = task.id;
             } else {
                 panic!("We don't expect failing to pick the task");
             }
+
+            /* The tick may be stopped by the idle task, restart it
+             * since we have something to run now. */
+            if !scheduler.is_idle(next) {
+                clint::tick_restart();
+            }
         } else {
             panic!("Fail to get scheduler lock for scheduler()");
         }
@@ -87,6 +130,10 @@ pub fn scheduler() {
          * may not return to scheduler() directly. */
         drop(scheduler_lock);
 
+        /* Since scheduler could be executed after timer interrupt, we
+         * need to avoid deadlock by enabling the interrupt again */
+        cpu::intr_on();
+
         unsafe {
             switch_to(kernel_task_context(), cur);
         }
@@ -117,3 +164,71 @@ pub fn do_sched() {
         switch_to(prev, kernel_task_context());
     }
 }
+
+// Switch out the current task and leave it in the given state
+fn sched_out(state: TaskState, wakeup: Option<usize>) {
+    /* Disable interrupt to avoid preemption after we change the state
+     * of task. The interrupt will be enabled by the scheduler. */
+    cpu::intr_off();
+    let cur = current();
+    unsafe {
+        (*cur).set_wakeup(wakeup);
+        (*cur).set_state(state);
+    }
+    do_sched();
+}
+
+// Put the current task to sleep for at least the given milliseconds
+pub fn sleep_ms(ms: usize) {
+    let wakeup = clint::get_time() + ms * (CLOCK_FREQ / 1000);
+    sched_out(TaskState::Sleeping, Some(wakeup));
+}
+
+/* Put the current task to sleep until it is woken up by wakeup() or
+ * signal. Note that the caller should disable interrupt before checking
+ * the condition to sleep, otherwise the wakeup may be lost. */
+pub fn sleep() {
+    sched_out(TaskState::Sleeping, None);
+}
+
+pub fn wakeup(pid: u32) {
+    SCHEDULER.lock().wakeup_task(TaskId(pid));
+}
+
+// Stop the current task until it is continued by SIGCONT
+pub fn do_stop() {
+    sched_out(TaskState::Stopped, None);
+}
+
+// Terminate the current task
+pub fn do_exit() -> ! {
+    // The unlinked files and cwd are released when they are closed
+    let cur = current();
+    unsafe {
+        fs::close_files((*cur).files());
+        fs::close_file((*cur).get_cwd());
+    }
+    sched_out(TaskState::Dead, None);
+    panic!("The dead task should never be scheduled");
+}
+
+pub fn send_signal(pid: u32, sig: usize) -> bool {
+    SCHEDULER.lock().send_signal(TaskId(pid), sig)
+}
+
+pub fn send_signal_group(pgid: u32, sig: usize) -> bool {
+    SCHEDULER.lock().send_signal_group(pgid, sig)
+}
+
+// Whether there's any task in the process group
+pub fn has_group(pgid: u32) -> bool {
+    SCHEDULER.lock().has_group(pgid)
+}
+
+pub fn set_pgid(pid: u32, pgid: u32) -> bool {
+    SCHEDULER.lock().set_pgid(TaskId(pid), pgid)
+}
+
+pub fn get_pgid(pid: u32) -> Option<u32> {
+    SCHEDULER.lock().get_pgid(TaskId(pid))
+}
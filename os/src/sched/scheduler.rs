@@ -1,20 +1,36 @@
 use core::ptr;
 
+use crate::sched::signal::{self, SIGCONT, SIGKILL};
 use crate::sched::task::{Task, TaskId, TaskState};
 use alloc::collections::VecDeque;
+use alloc::vec::Vec;
 
 use super::task::TaskType;
 
 pub struct Scheduler {
     tasks: VecDeque<Task>,
+    sleeping: Vec<Task>,
+    stopped: Vec<Task>,
+    /* The dead tasks can't be released until we leave their
+     * stack, so they are collected here and reaped later. */
+    dead: Vec<Task>,
     current: Option<Task>,
+    /* The idle task is not put in the run queue. It will only be
+     * picked when there is no other runnable task. */
+    idle: Option<Task>,
+    idle_id: Option<TaskId>,
 }
 
 impl Scheduler {
     pub fn new() -> Self {
         Scheduler {
             tasks: VecDeque::new(),
+            sleeping: Vec::new(),
+            stopped: Vec::new(),
+            dead: Vec::new(),
             current: None,
+            idle: None,
+            idle_id: None,
         }
     }
 
@@ -32,6 +48,19 @@ impl Scheduler {
         self.spawn(TaskType::User, func)
     }
 
+    pub fn set_idle(&mut self, func: extern "C" fn()) -> TaskId {
+        assert!(self.idle_id.is_none());
+
+        let (task, task_id) = Task::new(func, TaskType::Kernel);
+        self.idle = Some(task);
+        self.idle_id = Some(task_id);
+        task_id
+    }
+
+    pub fn is_idle(&self, task_id: TaskId) -> bool {
+        self.idle_id == Some(task_id)
+    }
+
     pub fn current(&mut self) -> *mut Task {
         /* FIXME: Trickly cast reference to raw pointer to
          * avoid the Rust lifetime check. Are there way to
@@ -46,9 +75,30 @@ impl Scheduler {
     pub fn put_prev(&mut self) -> Option<&Task> {
         /* Put current task back if there's any */
         if let Some(mut prev) = self.current.take() {
+            match prev.get_state() {
+                TaskState::Sleeping => {
+                    self.sleeping.push(prev);
+                    return self.sleeping.last();
+                }
+                TaskState::Stopped => {
+                    self.stopped.push(prev);
+                    return self.stopped.last();
+                }
+                TaskState::Dead => {
+                    self.dead.push(prev);
+                    return self.dead.last();
+                }
+                _ => {}
+            }
+
             assert!(matches!(prev.get_state(), TaskState::Running));
             prev.set_state(TaskState::Runnable);
 
+            if self.is_idle(prev.id) {
+                self.idle = Some(prev);
+                return self.idle.as_ref();
+            }
+
             self.tasks.push_back(prev);
             return self.tasks.back();
         }
@@ -56,13 +106,144 @@ impl Scheduler {
         None
     }
 
-    pub fn pick_next(&mut self) -> Option<&Task> {
+    // Move the sleeping tasks which are expired to the run queue
+    fn wakeup(&mut self, now: usize) {
+        let mut i = 0;
+        while i < self.sleeping.len() {
+            let expired = match self.sleeping[i].get_wakeup() {
+                Some(wakeup) => wakeup <= now,
+                None => false,
+            };
+
+            if expired {
+                let mut task = self.sleeping.swap_remove(i);
+                task.set_wakeup(None);
+                task.set_state(TaskState::Runnable);
+                self.tasks.push_back(task);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    // Release the dead tasks, this should not be called on their stack
+    pub fn reap(&mut self) {
+        self.dead.clear();
+    }
+
+    fn find_mut(&mut self, task_id: TaskId) -> Option<&mut Task> {
+        if let Some(cur) = self.current.as_mut() {
+            if cur.id == task_id {
+                return Some(cur);
+            }
+        }
+
+        self.tasks
+            .iter_mut()
+            .chain(self.sleeping.iter_mut())
+            .chain(self.stopped.iter_mut())
+            .find(|t| t.id == task_id)
+    }
+
+    // Move the task in the list back to run queue
+    fn resume(list: &mut Vec<Task>, tasks: &mut VecDeque<Task>, task_id: TaskId) {
+        if let Some(idx) = list.iter().position(|t| t.id == task_id) {
+            let mut task = list.swap_remove(idx);
+            task.set_wakeup(None);
+            task.set_state(TaskState::Runnable);
+            tasks.push_back(task);
+        }
+    }
+
+    // Wake up the task which is sleeping without timeout
+    pub fn wakeup_task(&mut self, task_id: TaskId) {
+        Self::resume(&mut self.sleeping, &mut self.tasks, task_id);
+    }
+
+    pub fn send_signal(&mut self, task_id: TaskId, sig: usize) -> bool {
+        assert!(signal::is_valid(sig));
+
+        let task = match self.find_mut(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+
+        // Signal is only meaningful for the user task
+        if !task.is_user() {
+            return false;
+        }
+        task.signal().post(sig);
+
+        /* Wake up the sleeping task, so the signal can be delivered
+         * as soon as possible. The stopped task is woken up only if the
+         * signal is going to continue or kill it. */
+        Self::resume(&mut self.sleeping, &mut self.tasks, task_id);
+        if sig == SIGCONT || sig == SIGKILL {
+            Self::resume(&mut self.stopped, &mut self.tasks, task_id);
+        }
+
+        true
+    }
+
+    // The user tasks in the process group
+    fn group_members(&self, pgid: u32) -> Vec<TaskId> {
+        self.current
+            .iter()
+            .chain(self.tasks.iter())
+            .chain(self.sleeping.iter())
+            .chain(self.stopped.iter())
+            .filter(|t| t.is_user() && t.get_pgid() == pgid)
+            .map(|t| t.id)
+            .collect()
+    }
+
+    pub fn send_signal_group(&mut self, pgid: u32, sig: usize) -> bool {
+        let ids = self.group_members(pgid);
+        for id in &ids {
+            self.send_signal(*id, sig);
+        }
+
+        !ids.is_empty()
+    }
+
+    pub fn has_group(&self, pgid: u32) -> bool {
+        !self.group_members(pgid).is_empty()
+    }
+
+    pub fn set_pgid(&mut self, task_id: TaskId, pgid: u32) -> bool {
+        match self.find_mut(task_id) {
+            Some(task) if task.is_user() => {
+                task.set_pgid(pgid);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get_pgid(&mut self, task_id: TaskId) -> Option<u32> {
+        self.find_mut(task_id).map(|t| t.get_pgid())
+    }
+
+    // The nearest time that a sleeping task should be woken up
+    pub fn next_wakeup(&self) -> Option<usize> {
+        self.sleeping.iter().filter_map(|t| t.get_wakeup()).min()
+    }
+
+    // Whether there's a task to run other than idle at the time
+    pub fn has_runnable(&self, now: usize) -> bool {
+        !self.tasks.is_empty() || self.next_wakeup().map_or(false, |t| t <= now)
+    }
+
+    pub fn pick_next(&mut self, now: usize) -> Option<&Task> {
         /* We should only pick a new task by explcitly
          * put back the current task first(if any). */
         assert!(self.current.is_none());
 
+        self.wakeup(now);
+
         // TODO: Add policy to pick the next task
-        if let Some(mut task) = self.tasks.pop_front() {
+        let task = self.tasks.pop_front().or_else(|| self.idle.take());
+        if let Some(mut task) = task {
             assert!(matches!(task.get_state(), TaskState::Runnable));
             task.set_state(TaskState::Running);
 
@@ -73,3 +254,73 @@ impl Scheduler {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn dummy() {}
+
+    #[test_case]
+    fn round_robin() {
+        let mut scheduler = Scheduler::new();
+        let a = scheduler.kspawn(dummy);
+        let b = scheduler.kspawn(dummy);
+
+        assert!(scheduler.pick_next(0).unwrap().id == a);
+        scheduler.put_prev();
+        assert!(scheduler.pick_next(0).unwrap().id == b);
+        scheduler.put_prev();
+        assert!(scheduler.pick_next(0).unwrap().id == a);
+    }
+
+    #[test_case]
+    fn idle_when_no_runnable() {
+        let mut scheduler = Scheduler::new();
+        let idle = scheduler.set_idle(dummy);
+
+        assert!(scheduler.pick_next(0).unwrap().id == idle);
+        scheduler.put_prev();
+
+        let a = scheduler.kspawn(dummy);
+        assert!(scheduler.pick_next(0).unwrap().id == a);
+    }
+
+    #[test_case]
+    fn sleep_until_expired() {
+        let mut scheduler = Scheduler::new();
+        let idle = scheduler.set_idle(dummy);
+        let a = scheduler.kspawn(dummy);
+
+        scheduler.pick_next(0);
+        let cur = unsafe { &mut *scheduler.current() };
+        cur.set_state(TaskState::Sleeping);
+        cur.set_wakeup(Some(100));
+        scheduler.put_prev();
+        assert_eq!(scheduler.next_wakeup(), Some(100));
+
+        assert!(!scheduler.has_runnable(99));
+        assert!(scheduler.pick_next(99).unwrap().id == idle);
+        scheduler.put_prev();
+        assert!(scheduler.has_runnable(100));
+        assert!(scheduler.pick_next(100).unwrap().id == a);
+    }
+
+    #[test_case]
+    fn runnable_after_wakeup() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_idle(dummy);
+        let a = scheduler.kspawn(dummy);
+
+        scheduler.pick_next(0);
+        let cur = unsafe { &mut *scheduler.current() };
+        cur.set_state(TaskState::Sleeping);
+        scheduler.put_prev();
+        assert!(!scheduler.has_runnable(0));
+
+        // The task woken up by others is runnable without timeout
+        scheduler.wakeup_task(a);
+        assert!(scheduler.has_runnable(0));
+        assert!(scheduler.pick_next(0).unwrap().id == a);
+    }
+}
@@ -0,0 +1,271 @@
+/* POSIX-style signal, the number and the structure follow
+ * https://elixir.bootlin.com/linux/latest/source/include/uapi/asm-generic/signal.h */
+use core::mem::size_of;
+
+use crate::config::SIGTRAMPOLINE_VA;
+use crate::sched;
+use crate::sched::task::Task;
+use crate::utils::cast::*;
+
+pub const NSIG: usize = 64;
+
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGBUS: usize = 7;
+pub const SIGKILL: usize = 9;
+pub const SIGSEGV: usize = 11;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGURG: usize = 23;
+pub const SIGWINCH: usize = 28;
+
+// The special value of sa_handler
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+// Don't block the signal itself while its handler is running
+pub const SA_NODEFER: usize = 0x4000_0000;
+// Reset the action to default once the handler is invoked
+pub const SA_RESETHAND: usize = 0x8000_0000;
+
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+pub type SigSet = u64;
+
+pub fn sigmask(sig: usize) -> SigSet {
+    1 << (sig - 1)
+}
+
+// SIGKILL and SIGSTOP can't be caught, blocked or ignored
+const UNCATCHABLE: SigSet = 1 << (SIGKILL - 1) | 1 << (SIGSTOP - 1);
+
+pub fn is_valid(sig: usize) -> bool {
+    sig >= 1 && sig <= NSIG
+}
+
+pub fn is_catchable(sig: usize) -> bool {
+    sigmask(sig) & UNCATCHABLE == 0
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SigAction {
+    pub handler: usize,
+    pub flags: usize,
+    pub mask: SigSet,
+}
+unsafe impl plain::Plain for SigAction {}
+
+/* The context of the interrupted user task, which is saved on the
+ * user stack before running the signal handler and restored by
+ * sigreturn. */
+#[repr(C)]
+#[derive(Default)]
+pub struct SigFrame {
+    pub regs: [usize; 32],
+    pub epc: usize,
+    pub blocked: SigSet,
+}
+unsafe impl plain::Plain for SigFrame {}
+
+enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+fn default_action(sig: usize) -> DefaultAction {
+    match sig {
+        SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        SIGCONT => DefaultAction::Continue,
+        _ => DefaultAction::Terminate,
+    }
+}
+
+pub struct SignalState {
+    pending: SigSet,
+    blocked: SigSet,
+    actions: [SigAction; NSIG],
+}
+
+impl SignalState {
+    pub fn new() -> Self {
+        SignalState {
+            pending: 0,
+            blocked: 0,
+            actions: [SigAction::default(); NSIG],
+        }
+    }
+
+    pub fn post(&mut self, sig: usize) {
+        assert!(is_valid(sig));
+
+        /* The stop signals and SIGCONT cancel each other, so the
+         * latest one takes effect. */
+        match default_action(sig) {
+            DefaultAction::Stop => self.pending &= !sigmask(SIGCONT),
+            DefaultAction::Continue => {
+                self.pending &=
+                    !(sigmask(SIGSTOP) | sigmask(SIGTSTP) | sigmask(SIGTTIN) | sigmask(SIGTTOU))
+            }
+            _ => {}
+        }
+
+        self.pending |= sigmask(sig);
+    }
+
+    /* Post the signal which is raised by the task itself, e.g. a fault. It
+     * can't be blocked or ignored, otherwise the task will trap forever. */
+    pub fn force(&mut self, sig: usize) {
+        let action = &mut self.actions[sig - 1];
+        if self.blocked & sigmask(sig) != 0 || action.handler == SIG_IGN {
+            self.blocked &= !sigmask(sig);
+            action.handler = SIG_DFL;
+        }
+        self.post(sig);
+    }
+
+    // Whether there is any signal which is going to be delivered
+    pub fn has_pending(&self) -> bool {
+        self.pending & !self.blocked != 0
+    }
+
+    // Take the lowest pending signal which is not blocked
+    fn take_pending(&mut self) -> Option<usize> {
+        let deliverable = self.pending & !self.blocked;
+        if deliverable == 0 {
+            return None;
+        }
+
+        let sig = deliverable.trailing_zeros() as usize + 1;
+        self.pending &= !sigmask(sig);
+        Some(sig)
+    }
+
+    pub fn get_action(&self, sig: usize) -> SigAction {
+        self.actions[sig - 1]
+    }
+
+    pub fn set_action(&mut self, sig: usize, action: SigAction) {
+        assert!(is_catchable(sig));
+        self.actions[sig - 1] = action;
+
+        // Discard the pending signal which is going to be ignored
+        if action.handler == SIG_IGN
+            || (action.handler == SIG_DFL && matches!(default_action(sig), DefaultAction::Ignore))
+        {
+            self.pending &= !sigmask(sig);
+        }
+    }
+
+    pub fn get_blocked(&self) -> SigSet {
+        self.blocked
+    }
+
+    pub fn set_blocked(&mut self, blocked: SigSet) {
+        self.blocked = blocked & !UNCATCHABLE;
+    }
+}
+
+/* Prepare the user context to run the signal handler. The interrupted
+ * context is pushed on the user stack, and the handler will return to
+ * the signal trampoline to invoke sigreturn. */
+fn setup_frame(task: &mut Task, sig: usize, action: &SigAction) -> bool {
+    let frame = task.frame();
+    let mut sigframe = SigFrame::default();
+    unsafe {
+        sigframe.regs = (*frame).regs;
+        sigframe.epc = (*frame).epc;
+    }
+    sigframe.blocked = task.signal().get_blocked();
+
+    let sp = unsafe { (*frame).get_sp() };
+    let sp = align_down!(sp - size_of::<SigFrame>(), 16);
+    if !task.mm().copy_to_user(sp, as_bytes(&sigframe)) {
+        return false;
+    }
+
+    let signal = task.signal();
+    let mut blocked = signal.get_blocked() | action.mask;
+    if action.flags & SA_NODEFER == 0 {
+        blocked |= sigmask(sig);
+    }
+    signal.set_blocked(blocked);
+
+    if action.flags & SA_RESETHAND != 0 {
+        signal.set_action(sig, SigAction::default());
+    }
+
+    unsafe {
+        (*frame).set_sp(sp);
+        (*frame).set_ra(SIGTRAMPOLINE_VA);
+        (*frame).set_a(0, sig);
+        (*frame).epc = action.handler;
+    }
+
+    true
+}
+
+// Restore the user context which is saved by setup_frame()
+pub fn restore_frame(task: &mut Task) -> bool {
+    let frame = task.frame();
+    let sp = unsafe { (*frame).get_sp() };
+
+    let mut sigframe = SigFrame::default();
+    if !task.mm().copy_from_user(sp, as_bytes_mut(&mut sigframe)) {
+        return false;
+    }
+
+    unsafe {
+        (*frame).regs = sigframe.regs;
+        (*frame).epc = sigframe.epc;
+    }
+    task.signal().set_blocked(sigframe.blocked);
+
+    true
+}
+
+/* Deliver the pending signals of the current task before it returns
+ * to the user space. */
+pub fn do_signal() {
+    loop {
+        let cur = unsafe { &mut *sched::current() };
+        let sig = match cur.signal().take_pending() {
+            Some(sig) => sig,
+            None => return,
+        };
+
+        let action = cur.signal().get_action(sig);
+        match action.handler {
+            SIG_IGN => continue,
+            SIG_DFL => match default_action(sig) {
+                DefaultAction::Ignore | DefaultAction::Continue => continue,
+                // Return here after the task is continued
+                DefaultAction::Stop => sched::do_stop(),
+                DefaultAction::Terminate => {
+                    warning!("Task {} is terminated by signal {}", cur.id.0, sig);
+                    sched::do_exit();
+                }
+            },
+            _ => {
+                /* Only one handler is setup at a time, the rest of the
+                 * signals will be delivered after sigreturn. */
+                if !setup_frame(cur, sig, &action) {
+                    warning!("Task {} fails to setup signal frame", cur.id.0);
+                    sched::do_exit();
+                }
+                return;
+            }
+        }
+    }
+}
@@ -1,11 +1,13 @@
-use core::mem::size_of;
+use core::mem::{self, size_of};
 use core::ptr;
 
 use crate::config::*;
+use crate::fs::{close_file, open_file, FileTable};
 use crate::mm::mapping::{Mapping, PteFlag, Segment};
 use crate::mm::page;
 use crate::order2size;
 use crate::sched::context::*;
+use crate::sched::signal::SignalState;
 use crate::sched::Locked;
 use crate::trap::user_trap_ret;
 use lazy_static::lazy_static;
@@ -21,10 +23,11 @@ pub enum TaskState {
     Runnable,
     Running,
     Sleeping,
+    Stopped,
     Dead,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct TaskId(pub u32);
 
 struct TaskIdAllocator {
@@ -67,6 +70,19 @@ pub struct Task {
     task_state: TaskState,
     func: extern "C" fn(),
     mm: Option<Mapping>,
+    // The time(in mtime) to wake up this task if it is sleeping
+    wakeup: Option<usize>,
+    signal: SignalState,
+    // The process group which the task belongs to
+    pgid: u32,
+    // The inode number of current working directory
+    cwd: u32,
+    /* The owner of the task, which is checked against the permission of
+     * files. Every task runs as root since there's no login yet. */
+    uid: u32,
+    gid: u32,
+    // The open files, indexed by the file descriptor
+    files: FileTable,
 
     kstack: *mut u8,
     ustack: *mut u8,
@@ -80,6 +96,7 @@ impl Task {
     fn init_mm(&mut self) {
         extern "C" {
             fn trampoline();
+            fn sigtrampoline();
         }
 
         if let Some(mapping) = &mut self.mm {
@@ -103,6 +120,15 @@ impl Task {
                 flags: PteFlag::EXECUTE | PteFlag::READ,
             });
 
+            /* Unlike the trampoline, the signal trampoline is executed
+             * in user mode when returning from the signal handler. */
+            mapping.map(Segment {
+                vaddr: SIGTRAMPOLINE_VA as u64,
+                paddr: sigtrampoline as u64,
+                len: PAGE_SIZE as u64,
+                flags: PteFlag::EXECUTE | PteFlag::READ | PteFlag::USER,
+            });
+
             mapping.map(Segment {
                 vaddr: TRAPFRAME_VA as u64,
                 paddr: self.context as u64,
@@ -170,12 +196,20 @@ impl Task {
             TaskType::User => Some(Mapping::new()),
         };
 
+        open_file(fs::ROOTINO);
         let mut task = Task {
             id,
             task_type,
             task_state: TaskState::Runnable,
             func,
             mm,
+            wakeup: None,
+            signal: SignalState::new(),
+            pgid: id.0,
+            cwd: fs::ROOTINO,
+            uid: fs::ROOT_UID,
+            gid: 0,
+            files: FileTable::new(),
             kstack,
             ustack,
             context,
@@ -218,6 +252,45 @@ impl Task {
         satp as usize
     }
 
+    pub fn is_user(&self) -> bool {
+        matches!(self.task_type, TaskType::User)
+    }
+
+    pub fn signal(&mut self) -> &mut SignalState {
+        &mut self.signal
+    }
+
+    pub fn files(&mut self) -> &mut FileTable {
+        &mut self.files
+    }
+
+    pub fn get_pgid(&self) -> u32 {
+        self.pgid
+    }
+
+    pub fn set_pgid(&mut self, pgid: u32) {
+        self.pgid = pgid;
+    }
+
+    pub fn get_cwd(&self) -> u32 {
+        self.cwd
+    }
+
+    /* The cwd is counted as an open file of the directory, so it is kept
+     * after removed until no task works in it. */
+    pub fn set_cwd(&mut self, cwd: u32) {
+        open_file(cwd);
+        close_file(mem::replace(&mut self.cwd, cwd));
+    }
+
+    pub fn get_uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn get_gid(&self) -> u32 {
+        self.gid
+    }
+
     pub fn get_state(&self) -> &TaskState {
         &self.task_state
     }
@@ -225,6 +298,14 @@ impl Task {
     pub fn set_state(&mut self, state: TaskState) {
         self.task_state = state;
     }
+
+    pub fn get_wakeup(&self) -> Option<usize> {
+        self.wakeup
+    }
+
+    pub fn set_wakeup(&mut self, wakeup: Option<usize>) {
+        self.wakeup = wakeup;
+    }
 }
 
 impl Drop for Task {
@@ -0,0 +1,66 @@
+// https://elixir.bootlin.com/linux/latest/source/include/uapi/asm-generic/errno-base.h
+// https://elixir.bootlin.com/linux/latest/source/include/uapi/asm-generic/errno.h
+use core::ffi::c_int;
+
+use fs::Error;
+
+// Operation not permitted
+pub const EPERM: c_int = 1;
+// No such file or directory
+pub const ENOENT: c_int = 2;
+// No such process
+pub const ESRCH: c_int = 3;
+// Bad file number
+pub const EBADF: c_int = 9;
+// Permission denied
+pub const EACCES: c_int = 13;
+// Bad address
+pub const EFAULT: c_int = 14;
+// Device or resource busy
+pub const EBUSY: c_int = 16;
+// File exists
+pub const EEXIST: c_int = 17;
+// Not a directory
+pub const ENOTDIR: c_int = 20;
+// Is a directory
+pub const EISDIR: c_int = 21;
+// Invalid argument
+pub const EINVAL: c_int = 22;
+// Too many open files
+pub const EMFILE: c_int = 24;
+// Not a typewriter
+pub const ENOTTY: c_int = 25;
+// File too large
+pub const EFBIG: c_int = 27;
+// No space left on device
+pub const ENOSPC: c_int = 28;
+// Illegal seek
+pub const ESPIPE: c_int = 29;
+// Too many links
+pub const EMLINK: c_int = 31;
+// Math result not representable
+pub const ERANGE: c_int = 34;
+// File name too long
+pub const ENAMETOOLONG: c_int = 36;
+// Directory not empty
+pub const ENOTEMPTY: c_int = 39;
+// Too many symbolic links encountered
+pub const ELOOP: c_int = 40;
+
+// Map the error of filesystem to the errno
+pub fn fs_errno(err: Error) -> c_int {
+    match err {
+        Error::NotFound => ENOENT,
+        Error::Exists => EEXIST,
+        Error::NotDir => ENOTDIR,
+        Error::IsDir => EISDIR,
+        Error::NotEmpty => ENOTEMPTY,
+        Error::NameTooLong => ENAMETOOLONG,
+        Error::Invalid => EINVAL,
+        Error::Busy => EBUSY,
+        Error::NotPermitted => EPERM,
+        Error::TooManyLinks => EMLINK,
+        Error::NoSpace => ENOSPC,
+        Error::Loop => ELOOP,
+    }
+}
@@ -1,6 +1,16 @@
+use core::ffi::c_int;
+
+use crate::fs::File;
 use crate::sched;
+use crate::utils::cast::*;
 
+mod errno;
+mod power;
 mod proc;
+mod signal;
+mod syslog;
+mod time;
+mod tty;
 pub mod types;
 
 // https://elixir.bootlin.com/linux/latest/source/include/uapi/asm-generic/unistd.h
@@ -11,18 +21,84 @@ const SYS_CLOSE: usize = 57;
 const SYS_READ: usize = 63;
 const SYS_WRITE: usize = 64;
 const SYS_MKNOD: usize = 33; // FIXME: 33 is for mknodat in fact
+const SYS_GETCWD: usize = 17;
+const SYS_CHDIR: usize = 49;
+const SYS_FCHDIR: usize = 50;
+const SYS_MKDIR: usize = 34; // FIXME: 34 is for mkdirat in fact
+const SYS_UNLINK: usize = 35; // FIXME: 35 is for unlinkat in fact
+const SYS_LINK: usize = 37; // FIXME: 37 is for linkat in fact
+const SYS_RENAME: usize = 38; // FIXME: 38 is for renameat in fact
+const SYS_SYMLINK: usize = 36; // FIXME: 36 is for symlinkat in fact
+const SYS_READLINK: usize = 78; // FIXME: 78 is for readlinkat in fact
+const SYS_STAT: usize = 79; // FIXME: 79 is for newfstatat in fact
+const SYS_FSTAT: usize = 80;
+const SYS_CHMOD: usize = 53; // FIXME: 53 is for fchmodat in fact
+const SYS_CHOWN: usize = 54; // FIXME: 54 is for fchownat in fact
+const SYS_UTIMES: usize = 88; // FIXME: 88 is for utimensat in fact
+const SYS_GETDENTS64: usize = 61;
+const SYS_LSEEK: usize = 62;
+const SYS_PREAD64: usize = 67;
+const SYS_PWRITE64: usize = 68;
+const SYS_TRUNCATE: usize = 45;
+const SYS_FTRUNCATE: usize = 46;
+const SYS_CLOCK_GETTIME: usize = 113;
+const SYS_GETTIMEOFDAY: usize = 169;
+const SYS_IOCTL: usize = 29;
+const SYS_KILL: usize = 129;
+const SYS_RT_SIGACTION: usize = 134;
+const SYS_RT_SIGPROCMASK: usize = 135;
+const SYS_RT_SIGRETURN: usize = 139;
+const SYS_SETPGID: usize = 154;
+const SYS_GETPGID: usize = 155;
+const SYS_SYSLOG: usize = 116;
+const SYS_REBOOT: usize = 142;
+const SYS_UPTIME: usize = 1000; // FIXME: This is not a Linux syscall
 
 pub fn syscall_handler() {
     let frame = sched::current_frame();
     // a7 is the number of syscall
     let syscall_num = unsafe { (*frame).get_a(7) };
 
-    info!("receive SYSCALL={}", syscall_num);
+    trace!("receive SYSCALL={}", syscall_num);
 
     let result = match syscall_num {
         SYS_OPEN => proc::sys_open() as usize,
+        SYS_CLOSE => proc::sys_close() as usize,
+        SYS_READ => proc::sys_read() as usize,
         SYS_WRITE => proc::sys_write() as usize,
         SYS_MKNOD => proc::sys_mknod() as usize,
+        SYS_GETCWD => proc::sys_getcwd() as usize,
+        SYS_CHDIR => proc::sys_chdir() as usize,
+        SYS_FCHDIR => proc::sys_fchdir() as usize,
+        SYS_MKDIR => proc::sys_mkdir() as usize,
+        SYS_UNLINK => proc::sys_unlink() as usize,
+        SYS_LINK => proc::sys_link() as usize,
+        SYS_RENAME => proc::sys_rename() as usize,
+        SYS_SYMLINK => proc::sys_symlink() as usize,
+        SYS_READLINK => proc::sys_readlink() as usize,
+        SYS_STAT => proc::sys_stat() as usize,
+        SYS_FSTAT => proc::sys_fstat() as usize,
+        SYS_CHMOD => proc::sys_chmod() as usize,
+        SYS_CHOWN => proc::sys_chown() as usize,
+        SYS_UTIMES => proc::sys_utimes() as usize,
+        SYS_GETDENTS64 => proc::sys_getdents64() as usize,
+        SYS_LSEEK => proc::sys_lseek() as usize,
+        SYS_PREAD64 => proc::sys_pread64() as usize,
+        SYS_PWRITE64 => proc::sys_pwrite64() as usize,
+        SYS_TRUNCATE => proc::sys_truncate() as usize,
+        SYS_FTRUNCATE => proc::sys_ftruncate() as usize,
+        SYS_CLOCK_GETTIME => time::sys_clock_gettime() as usize,
+        SYS_GETTIMEOFDAY => time::sys_gettimeofday() as usize,
+        SYS_UPTIME => time::sys_uptime() as usize,
+        SYS_KILL => signal::sys_kill() as usize,
+        SYS_RT_SIGACTION => signal::sys_rt_sigaction() as usize,
+        SYS_RT_SIGPROCMASK => signal::sys_rt_sigprocmask() as usize,
+        SYS_RT_SIGRETURN => signal::sys_rt_sigreturn(),
+        SYS_SETPGID => signal::sys_setpgid() as usize,
+        SYS_GETPGID => signal::sys_getpgid() as usize,
+        SYS_IOCTL => tty::sys_ioctl() as usize,
+        SYS_SYSLOG => syslog::sys_syslog() as usize,
+        SYS_REBOOT => power::sys_reboot() as usize,
         _ => panic!("Unknown syscall {}", syscall_num),
     };
 
@@ -35,3 +111,25 @@ pub(self) fn syscall_args(n: usize) -> usize {
     let frame = sched::current_frame();
     unsafe { (*frame).get_a(n) }
 }
+
+// Find the open file of the current process by the file descriptor
+pub(self) fn fd_file(fd: c_int) -> Option<&'static mut File> {
+    let cur = sched::current();
+    unsafe { (*cur).files().get(fd) }
+}
+
+/* The private function is used by syscall handler to copy the
+ * structure from the current process's memory space. */
+pub(self) fn copyin<T: plain::Plain>(addr: usize, t: &mut T) -> bool {
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    mm.copy_from_user(addr, as_bytes_mut(t))
+}
+
+/* The private function is used by syscall handler to copy the
+ * structure back to the current process's memory space. */
+pub(self) fn copyout<T: plain::Plain>(addr: usize, t: &T) -> bool {
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    mm.copy_to_user(addr, as_bytes(t))
+}
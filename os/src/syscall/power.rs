@@ -0,0 +1,39 @@
+use core::ffi::c_int;
+
+use crate::power;
+use crate::sched;
+use crate::syscall::errno::*;
+use crate::syscall::syscall_args;
+
+/* The magic numbers and commands of reboot, which follow
+ * https://elixir.bootlin.com/linux/latest/source/include/uapi/linux/reboot.h */
+const LINUX_REBOOT_MAGIC1: usize = 0xfee1dead;
+const LINUX_REBOOT_MAGIC2: usize = 672274793;
+const LINUX_REBOOT_CMD_RESTART: usize = 0x01234567;
+const LINUX_REBOOT_CMD_HALT: usize = 0xcdef0123;
+const LINUX_REBOOT_CMD_POWER_OFF: usize = 0x4321fedc;
+
+pub fn sys_reboot() -> c_int {
+    let magic1 = syscall_args(0) as u32 as usize;
+    let magic2 = syscall_args(1) as u32 as usize;
+    let cmd = syscall_args(2) as u32 as usize;
+    /* FIXME: Linux doesn't take the argument for power off, but we
+     * use it as the exit code of QEMU. */
+    let arg = syscall_args(3);
+
+    // Only the superuser can reboot or power off the machine
+    let cur = sched::current();
+    if unsafe { (*cur).get_uid() } != fs::ROOT_UID {
+        return -EPERM;
+    }
+
+    if magic1 != LINUX_REBOOT_MAGIC1 || magic2 != LINUX_REBOOT_MAGIC2 {
+        return -EINVAL;
+    }
+
+    match cmd {
+        LINUX_REBOOT_CMD_RESTART => power::reboot(),
+        LINUX_REBOOT_CMD_HALT | LINUX_REBOOT_CMD_POWER_OFF => power::poweroff(arg as u16),
+        _ => -EINVAL,
+    }
+}
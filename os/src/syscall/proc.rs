@@ -1,110 +1,264 @@
 use core::ffi::c_int;
+use core::mem::size_of;
 
 use fs::*;
 
+use crate::config::PAGE_SIZE;
 use crate::fs::*;
+use crate::mm::mapping::StrError;
 use crate::sched;
-use crate::syscall::syscall_args;
+use crate::syscall::errno::*;
 use crate::syscall::types::*;
-use crate::utils::cstr::*;
+use crate::syscall::{copyin, copyout, fd_file, syscall_args};
+use crate::time::{self, NSEC_PER_SEC, NSEC_PER_USEC};
+use crate::{console, tty};
 
 use alloc::string::String;
 use alloc::vec;
 
-/* The private function is used by syscall handler to access
- * the current process's memory space for nul-terminated string. */
-fn fetchstr(addr: usize) -> String {
+/* The private function is used by syscall handler to access the
+ * current process's memory space for nul-terminated string, which is
+ * at most `max` bytes including the '\0'. Return the errno if it can't
+ * be fetched. */
+fn fetchstr(addr: usize, max: usize) -> Result<String, c_int> {
     let cur = sched::current();
     let mm = unsafe { (*cur).mm() };
-    let mut buf = vec![0; MAXPATH];
-    let result = mm.copy_from_user(addr, &mut buf);
-    assert!(result);
+    let buf = match mm.copy_str_from_user(addr, max) {
+        Ok(buf) => buf,
+        Err(StrError::Fault) => return Err(-EFAULT),
+        Err(StrError::TooLong) => return Err(-ENAMETOOLONG),
+    };
 
-    buf2cstr(buf)
+    // FIXME: The filesystem only supports the name in UTF-8
+    String::from_utf8(buf).map_err(|_| -EINVAL)
 }
 
-// Seperate the last path entry from the path string
-fn path_to_parent_file(path: &str) -> Option<(&str, &str)> {
-    // TODO: The implementation should be fixed to meet the expected result
-    if path.len() == 0 {
-        return None;
+/* Fetch the path from the current process's memory space, or the
+ * errno if it is not a valid path. */
+fn fetchpath(addr: usize) -> Result<String, c_int> {
+    let path = fetchstr(addr, MAXPATH)?;
+    if name_too_long(&path) {
+        return Err(-ENAMETOOLONG);
     }
 
-    let path = path.trim();
-    if let Some((parent, file)) = path.rsplit_once('/') {
-        if parent == "" {
-            // Consider the case when parent is root
-            Some(("/", file))
-        } else {
-            Some((parent, file))
+    Ok(path)
+}
+
+// The current working directory of the current process
+fn cwd() -> u32 {
+    let cur = sched::current();
+    unsafe { (*cur).get_cwd() }
+}
+
+// The owner of the current process
+fn cred() -> (u32, u32) {
+    let cur = sched::current();
+    unsafe { ((*cur).get_uid(), (*cur).get_gid()) }
+}
+
+// Set the permission bits of the new inode owned by the current process
+fn init_owner(inode: &mut Inode, mode: mode_t) {
+    let (uid, gid) = cred();
+    inode.mode = mode as u32 & S_IALLUGO;
+    inode.uid = uid;
+    inode.gid = gid;
+}
+
+/* Check whether the current process can add or remove entries in the
+ * parent directory of path. The missing parent is left for the
+ * operation itself to report.
+ * FIXME: The search permission of directories along the path is not
+ * checked yet. */
+fn may_modify_parent(path: &str) -> Result<(), c_int> {
+    let (uid, gid) = cred();
+    match path_to_parent(cwd(), path) {
+        Some((parent, _)) if !parent.inner.permits(uid, gid, MAY_WRITE | MAY_EXEC) => Err(-EACCES),
+        _ => Ok(()),
+    }
+}
+
+pub fn sys_open() -> c_int {
+    let path_addr = syscall_args(0) as usize;
+    let flag = syscall_args(1) as c_int;
+    let mode = syscall_args(2) as mode_t;
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+
+    let follow = flag & O_NOFOLLOW == 0;
+    let mut inode = match lookup_inode(cwd(), &path, follow) {
+        Ok(_) if flag & (O_CREATE | O_EXCL) == O_CREATE | O_EXCL => return -EEXIST,
+        // The last element is a symbolic link with O_NOFOLLOW
+        Ok(inode) if inode.inner.typ == T_SYMLINK => return -ELOOP,
+        Ok(inode) => {
+            let mut mask = match flag & (O_WRONLY | O_RDWR) {
+                O_WRONLY => MAY_WRITE,
+                O_RDWR => MAY_READ | MAY_WRITE,
+                _ => MAY_READ,
+            };
+            if flag & O_TRUNC != 0 {
+                mask |= MAY_WRITE;
+            }
+            let (uid, gid) = cred();
+            if !inode.inner.permits(uid, gid, mask) {
+                return -EACCES;
+            }
+            inode
         }
-    } else {
-        Some(("", path))
+        /* The new file is opened with the access mode given, even if its
+         * permission bits don't allow it. */
+        Err(Error::NotFound) if flag & O_CREATE != 0 => {
+            if let Err(errno) = may_modify_parent(&path) {
+                return errno;
+            }
+            let inum = match mknod(cwd(), &path, T_FILE, 0, 0) {
+                Ok(inum) => inum,
+                Err(e) => return -fs_errno(e),
+            };
+            let mut inode = find_inode(inum);
+            init_owner(&mut inode.inner, mode);
+            inode
+        }
+        Err(e) => return -fs_errno(e),
+    };
+
+    // The directory can only be opened for reading
+    if inode.inner.typ == T_DIR && flag & (O_WRONLY | O_RDWR) != 0 {
+        return -EISDIR;
+    }
+    if inode.inner.typ == T_FILE && flag & O_TRUNC != 0 {
+        truncate(&mut inode, 0);
+    }
+
+    let cur = sched::current();
+    match unsafe { (*cur).files().alloc(File::new(inode.inum, flag)) } {
+        Some(fd) => {
+            open_file(inode.inum);
+            fd
+        }
+        None => -EMFILE,
+    }
+}
+
+pub fn sys_close() -> c_int {
+    let fd = syscall_args(0) as c_int;
+
+    let cur = sched::current();
+    match unsafe { (*cur).files().close(fd) } {
+        Some(file) => {
+            close_file(file.inum);
+            0
+        }
+        None => -EBADF,
     }
 }
 
-fn create(path: &str, typ: u16, major: u16, minor: u16) -> Option<FsInode> {
-    let (parent, file) = path_to_parent_file(path)?;
-    dbg!("Create file {} under {}", file, parent);
+// Read the console into the user buffer
+fn console_read(buf: usize, count: usize) -> isize {
+    let mut kbuf = vec![0; count.min(PAGE_SIZE)];
+    let n = match tty::read(&mut kbuf) {
+        Some(n) => n,
+        // Interrupted by signal
+        None => return -1,
+    };
 
-    let mut parent_inode = path_to_inode(parent)?;
-    if let Some(file_inode) = dirlookup(&parent_inode, file) {
-        // The inode for the file already exists
-        return Some(file_inode);
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    if !mm.copy_to_user(buf, &kbuf[0..n]) {
+        return -EFAULT as isize;
     }
 
-    /* Note that nlink of directory don't cosider itself(".").
-     * The purpose is to get rid of cyclic ref count */
-    let nlink = 1;
-    // Create inode for this new file/directory
-    let file_inum = alloc_inode(typ, major, minor, nlink);
-    let mut file_inode = find_inode(file_inum);
+    n as isize
+}
 
-    /* Link '.' and '..' to this new directory inode. */
-    if typ == T_DIR {
-        if !dirlink(&mut file_inode, ".", file_inum)
-            || !dirlink(&mut file_inode, "..", parent_inode.inum)
-        {
-            free_inode(file_inode);
-            return None;
+// Write the user buffer to the console
+fn console_write(buf: usize, count: usize) -> isize {
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    let mut kbuf = vec![0; PAGE_SIZE];
+    let mut total = 0;
+    while total < count {
+        let n = (count - total).min(PAGE_SIZE);
+        if !mm.copy_from_user(buf + total, &mut kbuf[0..n]) {
+            return -EFAULT as isize;
         }
+        console::write(&kbuf[0..n]);
+        total += n;
     }
 
-    /* Link this new file/directory to its parent directory. Do
-     * this after dirlink() the file_inode because it can simplify
-     * the error handling flow without rolling back the change
-     * on parent inode. */
-    if !dirlink(&mut parent_inode, file, file_inum) {
-        free_inode(file_inode);
-        return None;
+    total as isize
+}
+
+/* Read the regular file at the offset into the user buffer, and return
+ * the number of bytes read. It stops at the end of file. */
+fn file_read(inum: u32, off: usize, buf: usize, count: usize) -> isize {
+    let mut inode = find_inode(inum);
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    match readi_user(&mut inode, off, mm, buf, count) {
+        Some(n) => n as isize,
+        None => -EFAULT as isize,
     }
+}
 
-    /* Since parent("..") is linked by this directory, we should
-     * also update parent inode's nlink */
-    if typ == T_DIR {
-        parent_inode.inner.nlink += 1;
+/* Write the user buffer to the regular file at the offset, and return
+ * the number of bytes written. Writing beyond the end of file leaves a
+ * hole. It may stop early if the disk is full. */
+fn file_write(inum: u32, off: usize, buf: usize, count: usize) -> isize {
+    if count == 0 {
+        return 0;
+    }
+    // The file can't grow beyond the maximum size
+    if off >= MAX_FILE_SIZE {
+        return -EFBIG as isize;
     }
 
-    Some(file_inode)
+    let mut inode = find_inode(inum);
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    match writei_user(&mut inode, off, mm, buf, count) {
+        Some(0) => -ENOSPC as isize,
+        Some(n) => n as isize,
+        None => -EFAULT as isize,
+    }
 }
 
-pub fn sys_open() -> c_int {
-    let path_addr = syscall_args(0) as usize;
-    let flag = syscall_args(1) as c_int;
+fn readable(file: &File) -> bool {
+    file.flags & O_WRONLY == 0
+}
 
-    let path = fetchstr(path_addr);
+fn writable(file: &File) -> bool {
+    file.flags & (O_WRONLY | O_RDWR) != 0
+}
 
-    if flag & O_CREATE == O_CREATE {
-        todo!("sys_open O_CREATE");
-    } else {
-        let inode = path_to_inode(&path);
-        // The file is not existing
-        if inode.is_none() {
-            return -1;
-        }
+pub fn sys_read() -> isize {
+    let fd = syscall_args(0) as c_int;
+    let buf = syscall_args(1);
+    let count = syscall_args(2);
+
+    let file = match fd_file(fd) {
+        Some(file) => file,
+        // FIXME: The console is taken as stdin even if it's not opened
+        None if fd == 0 => return console_read(buf, count),
+        None => return -EBADF as isize,
+    };
+    if !readable(file) {
+        return -EBADF as isize;
     }
 
-    todo!("sys_open");
+    match inode_type(file.inum) {
+        T_DEVICE => console_read(buf, count),
+        T_DIR => -EISDIR as isize,
+        _ => {
+            let n = file_read(file.inum, file.off, buf, count);
+            if n > 0 {
+                file.off += n as usize;
+            }
+            n
+        }
+    }
 }
 
 pub fn sys_write() -> isize {
@@ -112,19 +266,649 @@ pub fn sys_write() -> isize {
     let buf = syscall_args(1);
     let count = syscall_args(2);
 
-    // TODO: Support the syscall correctly
+    let file = match fd_file(fd) {
+        Some(file) => file,
+        /* FIXME: The console is taken as stdout and stderr even if it's
+         * not opened. */
+        None if fd == 1 || fd == 2 => return console_write(buf, count),
+        None => return -EBADF as isize,
+    };
+    if !writable(file) {
+        return -EBADF as isize;
+    }
+
+    match inode_type(file.inum) {
+        T_DEVICE => console_write(buf, count),
+        _ => {
+            // Every write goes to the end of file in append mode
+            if file.flags & O_APPEND != 0 {
+                file.off = find_inode(file.inum).inner.size as usize;
+            }
 
-    return 0;
+            let n = file_write(file.inum, file.off, buf, count);
+            if n > 0 {
+                file.off += n as usize;
+            }
+            n
+        }
+    }
+}
+
+// Read the file at the offset, without changing the offset of file
+pub fn sys_pread64() -> isize {
+    let fd = syscall_args(0) as c_int;
+    let buf = syscall_args(1);
+    let count = syscall_args(2);
+    let off = syscall_args(3) as off_t;
+
+    let file = match fd_file(fd) {
+        Some(file) if readable(file) => file,
+        _ => return -EBADF as isize,
+    };
+    if off < 0 {
+        return -EINVAL as isize;
+    }
+
+    match inode_type(file.inum) {
+        T_DEVICE => -ESPIPE as isize,
+        T_DIR => -EISDIR as isize,
+        _ => file_read(file.inum, off as usize, buf, count),
+    }
+}
+
+/* Write the file at the offset, without changing the offset of file.
+ * The offset is used even in append mode, as POSIX requires. */
+pub fn sys_pwrite64() -> isize {
+    let fd = syscall_args(0) as c_int;
+    let buf = syscall_args(1);
+    let count = syscall_args(2);
+    let off = syscall_args(3) as off_t;
+
+    let file = match fd_file(fd) {
+        Some(file) if writable(file) => file,
+        _ => return -EBADF as isize,
+    };
+    if off < 0 {
+        return -EINVAL as isize;
+    }
+
+    match inode_type(file.inum) {
+        T_DEVICE => -ESPIPE as isize,
+        _ => file_write(file.inum, off as usize, buf, count),
+    }
+}
+
+/* Reposition the offset of file, and return the new offset. The offset
+ * can go beyond the end of file, and the next write leaves a hole. */
+pub fn sys_lseek() -> off_t {
+    let fd = syscall_args(0) as c_int;
+    let off = syscall_args(1) as off_t;
+    let whence = syscall_args(2) as c_int;
+
+    let file = match fd_file(fd) {
+        Some(file) => file,
+        None => return -EBADF as off_t,
+    };
+    let inode = find_inode(file.inum);
+    if inode.inner.typ == T_DEVICE {
+        return -ESPIPE as off_t;
+    }
+
+    let base = match whence {
+        SEEK_SET => 0,
+        SEEK_CUR => file.off as off_t,
+        SEEK_END => inode.inner.size as off_t,
+        _ => return -EINVAL as off_t,
+    };
+    let new_off = match base.checked_add(off) {
+        Some(new_off) if new_off >= 0 => new_off,
+        _ => return -EINVAL as off_t,
+    };
+
+    file.off = new_off as usize;
+    new_off
+}
+
+// Change the size of regular file, which should be writable by the caller
+fn truncate_inode(inode: &mut FsInode, length: off_t) -> c_int {
+    match inode.inner.typ {
+        T_DIR => return -EISDIR,
+        T_FILE => {}
+        _ => return -EINVAL,
+    }
+    if length < 0 {
+        return -EINVAL;
+    }
+
+    if !truncate(inode, length as usize) {
+        return -EFBIG;
+    }
+
+    0
+}
+
+pub fn sys_truncate() -> c_int {
+    let path_addr = syscall_args(0) as usize;
+    let length = syscall_args(1) as off_t;
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+
+    let mut inode = match lookup_inode(cwd(), &path, true) {
+        Ok(inode) => inode,
+        Err(e) => return -fs_errno(e),
+    };
+
+    let (uid, gid) = cred();
+    if inode.inner.typ == T_FILE && !inode.inner.permits(uid, gid, MAY_WRITE) {
+        return -EACCES;
+    }
+
+    truncate_inode(&mut inode, length)
+}
+
+pub fn sys_ftruncate() -> c_int {
+    let fd = syscall_args(0) as c_int;
+    let length = syscall_args(1) as off_t;
+
+    let file = match fd_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    if !writable(file) {
+        return -EINVAL;
+    }
+
+    truncate_inode(&mut find_inode(file.inum), length)
 }
 
 pub fn sys_mknod() -> c_int {
     let path_addr = syscall_args(0) as usize;
-    let _mode = syscall_args(1) as mode_t;
+    let mode = syscall_args(1) as mode_t;
     let dev = syscall_args(2) as dev_t;
 
-    let path = fetchstr(path_addr);
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+
+    if let Err(errno) = may_modify_parent(&path) {
+        return errno;
+    }
+
+    match mknod(cwd(), &path, T_DEVICE, MAJOR(dev), MINOR(dev)) {
+        Ok(inum) => {
+            init_owner(&mut find_inode(inum).inner, mode);
+            0
+        }
+        Err(e) => -fs_errno(e),
+    }
+}
+
+pub fn sys_chdir() -> c_int {
+    let path_addr = syscall_args(0) as usize;
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+
+    let inode = match lookup_inode(cwd(), &path, true) {
+        Ok(inode) => inode,
+        Err(e) => return -fs_errno(e),
+    };
+
+    if inode.inner.typ != T_DIR {
+        return -ENOTDIR;
+    }
+    // The directory should be searchable to be the cwd
+    let (uid, gid) = cred();
+    if !inode.inner.permits(uid, gid, MAY_EXEC) {
+        return -EACCES;
+    }
+
+    let cur = sched::current();
+    unsafe { (*cur).set_cwd(inode.inum) };
+
+    0
+}
+
+pub fn sys_fchdir() -> c_int {
+    let fd = syscall_args(0) as c_int;
+
+    let inode = match fd_file(fd) {
+        Some(file) => find_inode(file.inum),
+        None => return -EBADF,
+    };
+    if inode.inner.typ != T_DIR {
+        return -ENOTDIR;
+    }
+    let (uid, gid) = cred();
+    if !inode.inner.permits(uid, gid, MAY_EXEC) {
+        return -EACCES;
+    }
+
+    let cur = sched::current();
+    unsafe { (*cur).set_cwd(inode.inum) };
+
+    0
+}
+
+/* Copy the absolute path of the current working directory to the
+ * buffer, and return the length of it including the trailing '\0'. */
+pub fn sys_getcwd() -> isize {
+    let buf = syscall_args(0);
+    let size = syscall_args(1);
+
+    let mut path = match inode_path(cwd()) {
+        Some(path) => path.into_bytes(),
+        // The cwd is not reachable from root anymore
+        None => return -ENOENT as isize,
+    };
+    path.push(0);
+
+    if path.len() > size {
+        return -ERANGE as isize;
+    }
+
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    if !mm.copy_to_user(buf, &path) {
+        return -EFAULT as isize;
+    }
+
+    path.len() as isize
+}
+
+pub fn sys_mkdir() -> c_int {
+    let path_addr = syscall_args(0) as usize;
+    let mode = syscall_args(1) as mode_t;
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    if let Err(errno) = may_modify_parent(&path) {
+        return errno;
+    }
+
+    match mkdir(cwd(), &path) {
+        Ok(inum) => {
+            init_owner(&mut find_inode(inum).inner, mode);
+            0
+        }
+        Err(e) => -fs_errno(e),
+    }
+}
+
+// Remove the file, or the directory if AT_REMOVEDIR is set in flags
+pub fn sys_unlink() -> c_int {
+    let path_addr = syscall_args(0) as usize;
+    let flags = syscall_args(1) as c_int;
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    if let Err(errno) = may_modify_parent(&path) {
+        return errno;
+    }
+
+    let result = if flags & AT_REMOVEDIR != 0 {
+        rmdir(cwd(), &path)
+    } else {
+        unlink(cwd(), &path)
+    };
+    match result {
+        Ok(_) => 0,
+        Err(e) => -fs_errno(e),
+    }
+}
+
+pub fn sys_link() -> c_int {
+    let old_addr = syscall_args(0) as usize;
+    let new_addr = syscall_args(1) as usize;
+
+    let (old, new) = match (fetchpath(old_addr), fetchpath(new_addr)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(errno), _) | (_, Err(errno)) => return errno,
+    };
 
-    let _ = create(&path, T_DEVICE, MAJOR(dev), MINOR(dev));
+    if let Err(errno) = may_modify_parent(&new) {
+        return errno;
+    }
+
+    match link(cwd(), &old, &new) {
+        Ok(_) => 0,
+        Err(e) => -fs_errno(e),
+    }
+}
+
+pub fn sys_rename() -> c_int {
+    let old_addr = syscall_args(0) as usize;
+    let new_addr = syscall_args(1) as usize;
+
+    let (old, new) = match (fetchpath(old_addr), fetchpath(new_addr)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(errno), _) | (_, Err(errno)) => return errno,
+    };
+
+    if let Err(errno) = may_modify_parent(&old).and(may_modify_parent(&new)) {
+        return errno;
+    }
+
+    match rename(cwd(), &old, &new) {
+        Ok(_) => 0,
+        Err(e) => -fs_errno(e),
+    }
+}
+
+pub fn sys_symlink() -> c_int {
+    let target_addr = syscall_args(0) as usize;
+    let path_addr = syscall_args(1) as usize;
+
+    // The target is not a path to resolve now, so it is not checked
+    let target = match fetchstr(target_addr, SYMLINK_MAX + 1) {
+        Ok(target) => target,
+        Err(errno) => return errno,
+    };
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+
+    if let Err(errno) = may_modify_parent(&path) {
+        return errno;
+    }
+
+    match symlink(cwd(), &target, &path) {
+        Ok(inum) => {
+            init_owner(&mut find_inode(inum).inner, 0o777);
+            0
+        }
+        Err(e) => -fs_errno(e),
+    }
+}
+
+/* Copy the target of the symbolic link to the buffer without the
+ * trailing '\0', and return the number of bytes copied. The target
+ * is truncated if the buffer is too small. */
+pub fn sys_readlink() -> isize {
+    let path_addr = syscall_args(0) as usize;
+    let buf = syscall_args(1);
+    let size = syscall_args(2);
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno as isize,
+    };
+
+    let target = match readlink(cwd(), &path) {
+        Ok(target) => target.into_bytes(),
+        Err(e) => return -fs_errno(e) as isize,
+    };
+    let n = target.len().min(size);
+
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    if !mm.copy_to_user(buf, &target[..n]) {
+        return -EFAULT as isize;
+    }
+
+    n as isize
+}
+
+// Fill the stat structure with the information of inode
+fn fill_stat(inode: &FsInode) -> stat {
+    let inner = &inode.inner;
+    let typ = match inner.typ {
+        T_DIR => S_IFDIR,
+        T_SYMLINK => S_IFLNK,
+        T_DEVICE => S_IFCHR,
+        _ => S_IFREG,
+    };
+    stat {
+        st_ino: inode.inum as u64,
+        st_mode: typ as u32 | inner.mode,
+        st_nlink: inner.nlink as u32,
+        st_uid: inner.uid,
+        st_gid: inner.gid,
+        st_rdev: MKDEV(inner.major, inner.minor) as u64,
+        st_size: inner.size as i64,
+        st_blksize: BLKSZ as i32,
+        // The number of 512B blocks
+        st_blocks: (nblocks(inode) * BLKSZ / 512) as i64,
+        st_atime: (inner.atime / NSEC_PER_SEC) as i64,
+        st_atime_nsec: inner.atime % NSEC_PER_SEC,
+        st_mtime: (inner.mtime / NSEC_PER_SEC) as i64,
+        st_mtime_nsec: inner.mtime % NSEC_PER_SEC,
+        st_ctime: (inner.ctime / NSEC_PER_SEC) as i64,
+        st_ctime_nsec: inner.ctime % NSEC_PER_SEC,
+        ..Default::default()
+    }
+}
+
+pub fn sys_stat() -> c_int {
+    let path_addr = syscall_args(0) as usize;
+    let statbuf = syscall_args(1);
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+
+    let inode = match lookup_inode(cwd(), &path, true) {
+        Ok(inode) => inode,
+        Err(e) => return -fs_errno(e),
+    };
+
+    if !copyout(statbuf, &fill_stat(&inode)) {
+        return -EFAULT;
+    }
+
+    0
+}
+
+pub fn sys_fstat() -> c_int {
+    let fd = syscall_args(0) as c_int;
+    let statbuf = syscall_args(1);
+
+    let inode = match fd_file(fd) {
+        Some(file) => find_inode(file.inum),
+        None => return -EBADF,
+    };
+
+    if !copyout(statbuf, &fill_stat(&inode)) {
+        return -EFAULT;
+    }
+
+    0
+}
+
+// Only the owner or root can change the permission bits
+pub fn sys_chmod() -> c_int {
+    let path_addr = syscall_args(0) as usize;
+    let mode = syscall_args(1) as mode_t;
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+
+    let mut inode = match lookup_inode(cwd(), &path, true) {
+        Ok(inode) => inode,
+        Err(e) => return -fs_errno(e),
+    };
+
+    let (uid, _) = cred();
+    if uid != ROOT_UID && uid != inode.inner.uid {
+        return -EPERM;
+    }
+
+    inode.inner.mode = mode as u32 & S_IALLUGO;
+    inode.inner.ctime = time::realtime_ns();
+
+    0
+}
+
+/* Only root can change the owner of file. The owner or group is left
+ * unchanged if it is -1. */
+pub fn sys_chown() -> c_int {
+    let path_addr = syscall_args(0) as usize;
+    let owner = syscall_args(1) as uid_t;
+    let group = syscall_args(2) as gid_t;
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+
+    let mut inode = match lookup_inode(cwd(), &path, true) {
+        Ok(inode) => inode,
+        Err(e) => return -fs_errno(e),
+    };
+
+    let (uid, _) = cred();
+    if uid != ROOT_UID {
+        return -EPERM;
+    }
+
+    if owner != uid_t::MAX {
+        inode.inner.uid = owner;
+    }
+    if group != gid_t::MAX {
+        inode.inner.gid = group;
+    }
+    inode.inner.ctime = time::realtime_ns();
+
+    0
+}
+
+/* Set the access and modification time of file from the two timevals,
+ * or set both to the current time if times is NULL. Setting the current
+ * time only requires the write permission, but any other time requires
+ * the ownership. */
+pub fn sys_utimes() -> c_int {
+    let path_addr = syscall_args(0) as usize;
+    let times = syscall_args(1);
+
+    let path = match fetchpath(path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+
+    let mut inode = match lookup_inode(cwd(), &path, true) {
+        Ok(inode) => inode,
+        Err(e) => return -fs_errno(e),
+    };
+
+    let now = time::realtime_ns();
+    let (uid, gid) = cred();
+    let is_owner = uid == ROOT_UID || uid == inode.inner.uid;
+    let (atime, mtime) = if times == 0 {
+        if !is_owner && !inode.inner.permits(uid, gid, MAY_WRITE) {
+            return -EACCES;
+        }
+        (now, now)
+    } else {
+        if !is_owner {
+            return -EPERM;
+        }
+
+        let mut ns = [0; 2];
+        for (i, ns) in ns.iter_mut().enumerate() {
+            let mut tv = timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            };
+            if !copyin(times + i * size_of::<timeval>(), &mut tv) {
+                return -EFAULT;
+            }
+            if tv.tv_sec < 0 || !(0..1_000_000).contains(&tv.tv_usec) {
+                return -EINVAL;
+            }
+            *ns = tv.tv_sec as u64 * NSEC_PER_SEC + tv.tv_usec as u64 * NSEC_PER_USEC;
+        }
+        (ns[0], ns[1])
+    };
+
+    inode.inner.atime = atime;
+    inode.inner.mtime = mtime;
+    inode.inner.ctime = now;
+
+    0
+}
+
+// The offset of d_name in linux_dirent64
+const DIRENT64_NAME_OFF: usize = 19;
+
+/* Read the entries of directory from the offset of file into the buffer
+ * as linux_dirent64, and return the number of bytes filled, or 0 at the
+ * end of directory. The offset is advanced over the entries returned,
+ * so the next call continues from there. */
+pub fn sys_getdents64() -> isize {
+    let fd = syscall_args(0) as c_int;
+    let dirp = syscall_args(1);
+    let count = syscall_args(2);
+
+    let file = match fd_file(fd) {
+        Some(file) => file,
+        None => return -EBADF as isize,
+    };
+    let mut dir = find_inode(file.inum);
+    if dir.inner.typ != T_DIR {
+        return -ENOTDIR as isize;
+    }
+
+    let mut kbuf = vec![0; count.min(PAGE_SIZE)];
+    let mut len = 0;
+    // The directory may be changed since the offset was saved
+    let mut off = dirent_boundary(&dir, file.off);
+    while let Some(dirent) = read_dirent(&dir, off) {
+        let next = off + dirent.rec_len as usize;
+        if dirent.inum == 0 {
+            off = next;
+            continue;
+        }
+
+        // The name is nul-terminated, and the record is aligned to 8 bytes
+        let name = dirent.name();
+        let reclen = (DIRENT64_NAME_OFF + name.len() + 1 + 7) & !7;
+        if len + reclen > kbuf.len() {
+            // The buffer can't even hold one entry
+            if len == 0 {
+                return -EINVAL as isize;
+            }
+            break;
+        }
+
+        let d_type = match inode_type(dirent.inum) {
+            T_DIR => DT_DIR,
+            T_FILE => DT_REG,
+            T_DEVICE => DT_CHR,
+            T_SYMLINK => DT_LNK,
+            _ => DT_UNKNOWN,
+        };
+        let rec = &mut kbuf[len..len + reclen];
+        rec[0..8].copy_from_slice(&(dirent.inum as u64).to_le_bytes());
+        rec[8..16].copy_from_slice(&(next as i64).to_le_bytes());
+        rec[16..18].copy_from_slice(&(reclen as u16).to_le_bytes());
+        rec[18] = d_type;
+        rec[DIRENT64_NAME_OFF..DIRENT64_NAME_OFF + name.len()].copy_from_slice(name);
+
+        len += reclen;
+        off = next;
+    }
+
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    if !mm.copy_to_user(dirp, &kbuf[..len]) {
+        return -EFAULT as isize;
+    }
+    file.off = off;
+    dir.inner.atime = time::realtime_ns();
 
-    return 0;
+    len as isize
 }
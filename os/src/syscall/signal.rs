@@ -0,0 +1,157 @@
+use core::ffi::c_int;
+
+use crate::sched;
+use crate::sched::signal::*;
+use crate::syscall::errno::*;
+use crate::syscall::{copyin, copyout, syscall_args};
+
+pub fn sys_kill() -> c_int {
+    let pid = syscall_args(0) as c_int;
+    let sig = syscall_args(1);
+
+    if !is_valid(sig) {
+        return -EINVAL;
+    }
+
+    let result = if pid > 0 {
+        sched::send_signal(pid as u32, sig)
+    } else if pid == 0 {
+        // Send to the process group of the current task
+        let cur = sched::current();
+        let pgid = unsafe { (*cur).get_pgid() };
+        sched::send_signal_group(pgid, sig)
+    } else if pid < -1 {
+        sched::send_signal_group(-pid as u32, sig)
+    } else {
+        // TODO: Support to send signal to all the tasks
+        return -EINVAL;
+    };
+
+    if !result {
+        return -ESRCH;
+    }
+
+    0
+}
+
+pub fn sys_setpgid() -> c_int {
+    let pid = syscall_args(0) as c_int;
+    let pgid = syscall_args(1) as c_int;
+
+    if pgid < 0 {
+        return -EINVAL;
+    }
+    if pid < 0 {
+        return -ESRCH;
+    }
+
+    let cur = sched::current();
+    let pid = if pid == 0 {
+        unsafe { (*cur).id.0 }
+    } else {
+        pid as u32
+    };
+    // The process group is led by the task itself if pgid is zero
+    let pgid = if pgid == 0 { pid } else { pgid as u32 };
+
+    if !sched::set_pgid(pid, pgid) {
+        return -ESRCH;
+    }
+
+    0
+}
+
+pub fn sys_getpgid() -> c_int {
+    let pid = syscall_args(0) as c_int;
+
+    if pid < 0 {
+        return -ESRCH;
+    }
+
+    let cur = sched::current();
+    let pid = if pid == 0 {
+        unsafe { (*cur).id.0 }
+    } else {
+        pid as u32
+    };
+
+    match sched::get_pgid(pid) {
+        Some(pgid) => pgid as c_int,
+        None => -ESRCH,
+    }
+}
+
+pub fn sys_rt_sigaction() -> c_int {
+    let sig = syscall_args(0);
+    let act = syscall_args(1);
+    let oldact = syscall_args(2);
+
+    if !is_valid(sig) {
+        return -EINVAL;
+    }
+
+    let cur = sched::current();
+    let signal = unsafe { (*cur).signal() };
+
+    if oldact != 0 && !copyout(oldact, &signal.get_action(sig)) {
+        return -EFAULT;
+    }
+
+    if act != 0 {
+        if !is_catchable(sig) {
+            return -EPERM;
+        }
+
+        let mut action = SigAction::default();
+        if !copyin(act, &mut action) {
+            return -EFAULT;
+        }
+        signal.set_action(sig, action);
+    }
+
+    0
+}
+
+pub fn sys_rt_sigprocmask() -> c_int {
+    let how = syscall_args(0);
+    let set = syscall_args(1);
+    let oldset = syscall_args(2);
+
+    let cur = sched::current();
+    let signal = unsafe { (*cur).signal() };
+    let blocked = signal.get_blocked();
+
+    if oldset != 0 && !copyout(oldset, &blocked) {
+        return -EFAULT;
+    }
+
+    if set != 0 {
+        let mut mask: SigSet = 0;
+        if !copyin(set, &mut mask) {
+            return -EFAULT;
+        }
+
+        let blocked = match how {
+            SIG_BLOCK => blocked | mask,
+            SIG_UNBLOCK => blocked & !mask,
+            SIG_SETMASK => mask,
+            _ => return -EINVAL,
+        };
+        signal.set_blocked(blocked);
+    }
+
+    0
+}
+
+pub fn sys_rt_sigreturn() -> usize {
+    let cur = unsafe { &mut *sched::current() };
+
+    if !restore_frame(cur) {
+        warning!("Task {} fails to restore signal frame", cur.id.0);
+        sched::do_exit();
+    }
+
+    /* The return value of syscall will be written to a0, so we
+     * return the restored a0 to keep it unchanged. */
+    unsafe { (*cur.frame()).get_a(0) }
+}
@@ -0,0 +1,122 @@
+use alloc::vec;
+use core::ffi::c_int;
+use core::str;
+
+use crate::log::{self, Level};
+use crate::mm::mapping::StrError;
+use crate::sched;
+use crate::syscall::errno::*;
+use crate::syscall::syscall_args;
+
+/* The actions of syslog, which follow
+ * https://elixir.bootlin.com/linux/latest/source/include/linux/syslog.h */
+const SYSLOG_ACTION_READ: usize = 2;
+const SYSLOG_ACTION_READ_ALL: usize = 3;
+const SYSLOG_ACTION_READ_CLEAR: usize = 4;
+const SYSLOG_ACTION_CLEAR: usize = 5;
+const SYSLOG_ACTION_CONSOLE_LEVEL: usize = 8;
+const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+/* Our own action which is not in Linux. It sets the threshold of the
+ * module at `bufp`, whose path is like "os::fs", to the loglevel `len`.
+ * The empty path is for the modules without their own threshold. */
+const SYSLOG_ACTION_MODULE_LEVEL: usize = 100;
+
+// Maximum length for the path of module, including the trailing '\0'
+const MODULE_PATH_MAX: usize = 64;
+
+/* Translate the console loglevel of Linux to our log level. Linux prints
+ * the messages whose loglevel is less than it, e.g. 4 for up to KERN_ERR
+ * and 7 for up to KERN_INFO. The levels above KERN_ERR are taken as our
+ * Error, and KERN_NOTICE as our Info. We take 9, which is out of range
+ * in Linux, for Trace. */
+fn to_level(n: usize) -> Option<Level> {
+    match n {
+        1..=4 => Some(Level::Error),
+        5 => Some(Level::Warn),
+        6..=7 => Some(Level::Info),
+        8 => Some(Level::Debug),
+        9 => Some(Level::Trace),
+        _ => None,
+    }
+}
+
+fn set_module_level(bufp: usize, level: Level) -> c_int {
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    let module = match mm.copy_str_from_user(bufp, MODULE_PATH_MAX) {
+        Ok(module) => module,
+        Err(StrError::Fault) => return -EFAULT,
+        Err(StrError::TooLong) => return -EINVAL,
+    };
+    let module = match str::from_utf8(&module) {
+        Ok(module) => module,
+        Err(_) => return -EINVAL,
+    };
+
+    if module.is_empty() {
+        log::set_default_level(level);
+    } else if !log::set_level(module, level) {
+        return -ENOSPC;
+    }
+
+    0
+}
+
+fn copy_log(bufp: usize, len: usize, read: fn(&mut [u8]) -> usize) -> c_int {
+    let mut buf = vec![0; len.min(log::buf_size())];
+    let n = read(&mut buf);
+
+    let cur = sched::current();
+    let mm = unsafe { (*cur).mm() };
+    if !mm.copy_to_user(bufp, &buf[..n]) {
+        return -EFAULT;
+    }
+
+    n as c_int
+}
+
+pub fn sys_syslog() -> c_int {
+    let action = syscall_args(0);
+    let bufp = syscall_args(1);
+    let len = syscall_args(2) as c_int;
+
+    match action {
+        /* FIXME: Linux blocks the reader until the log ring is not
+         * empty, but we just return 0 for this case now. */
+        SYSLOG_ACTION_READ => {
+            if len < 0 {
+                return -EINVAL;
+            }
+            copy_log(bufp, len as usize, log::read)
+        }
+        SYSLOG_ACTION_READ_ALL | SYSLOG_ACTION_READ_CLEAR => {
+            if len < 0 {
+                return -EINVAL;
+            }
+            let n = copy_log(bufp, len as usize, log::read_all);
+            if n >= 0 && action == SYSLOG_ACTION_READ_CLEAR {
+                log::clear();
+            }
+            n
+        }
+        SYSLOG_ACTION_CLEAR => {
+            log::clear();
+            0
+        }
+        SYSLOG_ACTION_CONSOLE_LEVEL => match to_level(len as usize) {
+            Some(level) => {
+                log::set_console_level(level);
+                0
+            }
+            None => -EINVAL,
+        },
+        SYSLOG_ACTION_MODULE_LEVEL => match to_level(len as usize) {
+            Some(level) => set_module_level(bufp, level),
+            None => -EINVAL,
+        },
+        SYSLOG_ACTION_SIZE_UNREAD => log::unread_size() as c_int,
+        SYSLOG_ACTION_SIZE_BUFFER => log::buf_size() as c_int,
+        _ => -EINVAL,
+    }
+}
@@ -0,0 +1,50 @@
+use core::ffi::{c_int, c_long};
+
+use crate::syscall::errno::*;
+use crate::syscall::types::*;
+use crate::syscall::{copyout, syscall_args};
+use crate::time::*;
+
+pub fn sys_clock_gettime() -> c_int {
+    let clockid = syscall_args(0) as clockid_t;
+    let tp = syscall_args(1);
+
+    let ns = match clockid {
+        CLOCK_REALTIME => realtime_ns(),
+        CLOCK_MONOTONIC => monotonic_ns(),
+        _ => return -EINVAL,
+    };
+
+    let ts = timespec {
+        tv_sec: (ns / NSEC_PER_SEC) as time_t,
+        tv_nsec: (ns % NSEC_PER_SEC) as c_long,
+    };
+
+    if !copyout(tp, &ts) {
+        return -EFAULT;
+    }
+
+    0
+}
+
+pub fn sys_gettimeofday() -> c_int {
+    let tv = syscall_args(0);
+    // The timezone argument is obsolete, so we don't care about it
+
+    let ns = realtime_ns();
+    let val = timeval {
+        tv_sec: (ns / NSEC_PER_SEC) as time_t,
+        tv_usec: ((ns % NSEC_PER_SEC) / NSEC_PER_USEC) as suseconds_t,
+    };
+
+    if !copyout(tv, &val) {
+        return -EFAULT;
+    }
+
+    0
+}
+
+// Return the milliseconds since the system boot
+pub fn sys_uptime() -> u64 {
+    monotonic_ns() / NSEC_PER_MSEC
+}
@@ -0,0 +1,57 @@
+use core::ffi::c_int;
+
+use crate::syscall::errno::*;
+use fs::T_DEVICE;
+
+use crate::fs::inode_type;
+use crate::sched;
+use crate::syscall::{copyin, copyout, fd_file, syscall_args};
+use crate::tty;
+
+// Get the foreground process group of terminal
+const TIOCGPGRP: usize = 0x540f;
+// Set the foreground process group of terminal
+const TIOCSPGRP: usize = 0x5410;
+
+pub fn sys_ioctl() -> c_int {
+    let fd = syscall_args(0) as c_int;
+    let request = syscall_args(1);
+    let argp = syscall_args(2);
+
+    match fd_file(fd) {
+        Some(file) if inode_type(file.inum) != T_DEVICE => return -ENOTTY,
+        Some(_) => {}
+        /* FIXME: The console is taken as the standard streams even if
+         * they are not opened. */
+        None if (0..=2).contains(&fd) => {}
+        None => return -EBADF,
+    }
+
+    match request {
+        TIOCGPGRP => {
+            let pgrp = match tty::get_fg_pgrp() {
+                Some(pgrp) => pgrp as c_int,
+                None => return -ENOTTY,
+            };
+            if !copyout(argp, &pgrp) {
+                return -EFAULT;
+            }
+        }
+        TIOCSPGRP => {
+            let mut pgrp: c_int = 0;
+            if !copyin(argp, &mut pgrp) {
+                return -EFAULT;
+            }
+            if pgrp <= 0 {
+                return -EINVAL;
+            }
+            if !sched::has_group(pgrp as u32) {
+                return -EPERM;
+            }
+            tty::set_fg_pgrp(pgrp as u32);
+        }
+        _ => return -ENOTTY,
+    }
+
+    0
+}
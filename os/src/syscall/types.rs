@@ -1,7 +1,7 @@
 #![allow(non_snake_case)]
 #![allow(non_camel_case_types)]
 
-use core::ffi::c_int;
+use core::ffi::{c_int, c_long};
 
 pub const MINORBITS: c_int = 20;
 pub const MINORMASK: c_int = (1 << MINORBITS) - 1;
@@ -14,5 +14,79 @@ pub fn MINOR(dev: c_int) -> u16 {
     (dev as c_int & MINORMASK) as u16
 }
 
+pub fn MKDEV(major: u16, minor: u16) -> dev_t {
+    (major as c_int) << MINORBITS | minor as c_int
+}
+
 pub type mode_t = c_int;
 pub type dev_t = c_int;
+pub type uid_t = u32;
+pub type gid_t = u32;
+
+// The type of file in st_mode
+pub const S_IFLNK: mode_t = 0o120000;
+pub const S_IFREG: mode_t = 0o100000;
+pub const S_IFDIR: mode_t = 0o040000;
+pub const S_IFCHR: mode_t = 0o020000;
+
+// The type of file in linux_dirent64
+pub const DT_UNKNOWN: u8 = 0;
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+pub const DT_LNK: u8 = 10;
+
+pub type off_t = i64;
+
+// The whence of lseek
+pub const SEEK_SET: c_int = 0;
+pub const SEEK_CUR: c_int = 1;
+pub const SEEK_END: c_int = 2;
+
+pub type time_t = i64;
+pub type suseconds_t = i64;
+pub type clockid_t = c_int;
+
+pub const CLOCK_REALTIME: clockid_t = 0;
+pub const CLOCK_MONOTONIC: clockid_t = 1;
+
+#[repr(C)]
+pub struct timespec {
+    pub tv_sec: time_t,
+    pub tv_nsec: c_long,
+}
+unsafe impl plain::Plain for timespec {}
+
+#[repr(C)]
+pub struct timeval {
+    pub tv_sec: time_t,
+    pub tv_usec: suseconds_t,
+}
+unsafe impl plain::Plain for timeval {}
+
+// https://elixir.bootlin.com/linux/latest/source/include/uapi/asm-generic/stat.h
+#[repr(C)]
+#[derive(Default)]
+pub struct stat {
+    pub st_dev: u64,
+    pub st_ino: u64,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    pub __pad1: u64,
+    pub st_size: i64,
+    pub st_blksize: i32,
+    pub __pad2: i32,
+    pub st_blocks: i64,
+    pub st_atime: i64,
+    pub st_atime_nsec: u64,
+    pub st_mtime: i64,
+    pub st_mtime_nsec: u64,
+    pub st_ctime: i64,
+    pub st_ctime_nsec: u64,
+    pub __unused4: u32,
+    pub __unused5: u32,
+}
+unsafe impl plain::Plain for stat {}
@@ -0,0 +1,28 @@
+/* The harness of in-kernel tests. They are collected by the custom test
+ * framework and run after the kernel is initialized. The result is
+ * reported by the exit code of QEMU, so `cargo test` can tell whether
+ * they pass. A failed test panics, which powers off with failure. */
+use crate::power;
+use core::any::type_name;
+
+pub trait Testable {
+    fn run(&self);
+}
+
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        print!("test {} ... ", type_name::<T>());
+        self();
+        println!("\x1b[1;32mok\x1b[0m");
+    }
+}
+
+pub fn runner(tests: &[&dyn Testable]) {
+    println!("running {} tests", tests.len());
+    for test in tests {
+        test.run();
+    }
+    println!("test result: ok. {} passed", tests.len());
+
+    power::poweroff(0);
+}
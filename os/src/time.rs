@@ -0,0 +1,45 @@
+use crate::config::CLOCK_FREQ;
+use crate::{clint, rtc};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub const NSEC_PER_SEC: u64 = 1_000_000_000;
+pub const NSEC_PER_USEC: u64 = 1_000;
+pub const NSEC_PER_MSEC: u64 = 1_000_000;
+
+// The interval to synchronize the realtime with RTC
+const RTC_SYNC_INTERVAL_NS: u64 = 60 * NSEC_PER_SEC;
+
+// The realtime(nanoseconds since epoch) when mtime is zero
+static BOOT_EPOCH_NS: AtomicU64 = AtomicU64::new(0);
+
+// Convert the value of mtime to nanoseconds
+pub fn mtime_to_ns(mtime: usize) -> u64 {
+    (mtime as u128 * NSEC_PER_SEC as u128 / CLOCK_FREQ as u128) as u64
+}
+
+// Nanoseconds since the system boot
+pub fn monotonic_ns() -> u64 {
+    mtime_to_ns(clint::get_time())
+}
+
+// Nanoseconds since epoch
+pub fn realtime_ns() -> u64 {
+    BOOT_EPOCH_NS.load(Ordering::Relaxed) + monotonic_ns()
+}
+
+pub fn set_realtime_ns(ns: u64) {
+    BOOT_EPOCH_NS.store(ns.saturating_sub(monotonic_ns()), Ordering::Relaxed);
+}
+
+/* Since mtime may drift from the realtime, we synchronize with
+ * RTC periodically by its alarm. */
+fn rtc_sync() {
+    let now = rtc::read_time();
+    set_realtime_ns(now);
+    rtc::set_alarm(now + RTC_SYNC_INTERVAL_NS, rtc_sync);
+}
+
+pub fn init() {
+    // Take the time from RTC as the realtime of the system boot
+    rtc_sync();
+}
@@ -1,4 +1,6 @@
 use crate::config::{TRAMPOLINE_VA, TRAPFRAME_VA};
+use crate::panic::{self, TrapInfo};
+use crate::sched::signal::{self, SIGBUS, SIGILL, SIGSEGV, SIGTRAP};
 use crate::{clint, cpu, plic, sched, syscall};
 
 use mcause::{Interrupt as mInterrupt, Trap as mTrap};
@@ -23,7 +25,7 @@ pub fn timer_trap_handler() {
 }
 
 #[no_mangle]
-pub fn kernel_trap_handler() {
+pub extern "C" fn kernel_trap_handler(regs: &[usize; 32]) {
     let sepc = sepc::read();
     let sstatus = cpu::r_sstatus();
     let stval = stval::read();
@@ -39,12 +41,23 @@ pub fn kernel_trap_handler() {
         sTrap::Exception(sException::UserEnvCall) => {
             todo!()
         }
-        _ => panic!(
-            "S=Interrupted: {:?}, {:X} {:X}",
-            scause.cause(),
-            stval,
-            sepc
-        ),
+        _ => {
+            let mut regs = *regs;
+            // The registers are saved after kernelvec makes room on stack
+            regs[2] += 8 * 33;
+            panic::set_trap_info(TrapInfo {
+                regs,
+                epc: sepc,
+                cause: scause.bits(),
+                tval: stval,
+            });
+            panic!(
+                "S=Interrupted: {:?}, {:X} {:X}",
+                scause.cause(),
+                stval,
+                sepc
+            );
+        }
     }
 
     /* Since we may overwrite sepc and sstatus for other traps
@@ -91,12 +104,42 @@ pub fn user_trap_handler() {
 
             syscall::syscall_handler();
         }
-        _ => panic!(
-            "U=Interrupted: {:?}, {:X} {:X}",
-            scause.cause(),
-            stval,
-            sepc
-        ),
+        /* The exceptions caused by user task are reported to itself
+         * by signal instead of panicking the kernel. */
+        sTrap::Exception(e) => {
+            let sig = match e {
+                sException::IllegalInstruction => SIGILL,
+                sException::Breakpoint => SIGTRAP,
+                sException::InstructionMisaligned | sException::StoreMisaligned => SIGBUS,
+                _ => SIGSEGV,
+            };
+            warning!(
+                "U=Exception: {:?}, {:X} {:X}, send signal {}",
+                e,
+                stval,
+                sepc,
+                sig
+            );
+
+            let cur = sched::current();
+            unsafe {
+                (*cur).signal().force(sig);
+            }
+        }
+        _ => {
+            panic::set_trap_info(TrapInfo {
+                regs: unsafe { (*frame).regs },
+                epc: sepc,
+                cause: scause.bits(),
+                tval: stval,
+            });
+            panic!(
+                "U=Interrupted: {:?}, {:X} {:X}",
+                scause.cause(),
+                stval,
+                sepc
+            );
+        }
     }
 
     user_trap_ret();
@@ -112,6 +155,10 @@ pub fn user_trap_ret() -> ! {
         fn userret();
     }
 
+    /* Deliver the pending signals before going back to the userspace,
+     * this may not return if the task is terminated. */
+    signal::do_signal();
+
     let current = sched::current();
 
     /* Disable interrupts until we're goinh to back
@@ -0,0 +1,207 @@
+/* The line discipline of console, which provides the canonical mode
+ * input like a terminal: the input is edited in line and only
+ * available to the reader after the line is completed. The control
+ * characters are also handled to send signal to the foreground
+ * process group. */
+use crate::cpu;
+use crate::lock::Locked;
+use crate::sched;
+use crate::sched::signal::{SIGINT, SIGQUIT, SIGTSTP};
+use crate::utils::ringbuf::RingBuf;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+// The maximum length of a line, the extra characters are discarded
+const MAX_LINE: usize = 255;
+
+const fn ctrl(c: u8) -> u8 {
+    c & 0x1f
+}
+
+// ^C: interrupt the foreground process group
+const VINTR: u8 = ctrl(b'C');
+// ^\: quit the foreground process group
+const VQUIT: u8 = ctrl(b'\\');
+// ^Z: stop the foreground process group
+const VSUSP: u8 = ctrl(b'Z');
+// ^D: end of file
+const VEOF: u8 = ctrl(b'D');
+// ^U: erase the whole line
+const VKILL: u8 = ctrl(b'U');
+// ^H or DEL: erase the last character
+const VERASE: u8 = 0x7f;
+const BACKSPACE: u8 = ctrl(b'H');
+
+// The control character other than newline and tab is echoed as "^X"
+fn is_caret(c: u8) -> bool {
+    c.is_ascii_control() && c != b'\n' && c != b'\t'
+}
+
+struct Tty {
+    // The line which is still editing
+    line: Vec<u8>,
+    // The completed lines which are ready to be read
+    read_buf: RingBuf<u8, 512>,
+    // The number of EOF which are not consumed by reader
+    eof: usize,
+    // The tasks which are waiting for input
+    readers: Vec<u32>,
+    // The process group to receive the signal from console
+    fg_pgrp: Option<u32>,
+}
+
+impl Tty {
+    fn new() -> Self {
+        Tty {
+            line: Vec::new(),
+            read_buf: RingBuf::new(),
+            eof: 0,
+            readers: Vec::new(),
+            fg_pgrp: None,
+        }
+    }
+
+    fn echo(&self, c: u8) {
+        if is_caret(c) {
+            print!("^{}", (c + b'@') as char);
+        } else {
+            print!("{}", c as char);
+        }
+    }
+
+    fn erase(&mut self) {
+        if let Some(c) = self.line.pop() {
+            // The control character is echoed with two characters
+            if is_caret(c) {
+                print!("\x08\x08  \x08\x08");
+            } else {
+                print!("\x08 \x08");
+            }
+        }
+    }
+
+    // Move the line to read buffer and wake up the readers
+    fn commit(&mut self) {
+        for c in self.line.drain(..) {
+            self.read_buf.push(c);
+        }
+
+        for pid in self.readers.drain(..) {
+            sched::wakeup(pid);
+        }
+    }
+
+    fn signal(&mut self, c: u8, sig: usize) {
+        self.echo(c);
+        print!("\n");
+        self.line.clear();
+
+        if let Some(pgrp) = self.fg_pgrp {
+            sched::send_signal_group(pgrp, sig);
+        }
+    }
+
+    fn input(&mut self, c: u8) {
+        // Translate carriage return from the serial line to newline
+        let c = if c == b'\r' { b'\n' } else { c };
+
+        match c {
+            VINTR => self.signal(c, SIGINT),
+            VQUIT => self.signal(c, SIGQUIT),
+            VSUSP => self.signal(c, SIGTSTP),
+            VERASE | BACKSPACE => self.erase(),
+            VKILL => {
+                while !self.line.is_empty() {
+                    self.erase();
+                }
+            }
+            VEOF => {
+                /* EOF makes the partial line readable without newline,
+                 * or let the reader get nothing if the line is empty. */
+                if self.line.is_empty() {
+                    self.eof += 1;
+                }
+                self.commit();
+            }
+            b'\n' => {
+                self.echo(c);
+                self.line.push(c);
+                self.commit();
+            }
+            c => {
+                if self.line.len() < MAX_LINE {
+                    self.echo(c);
+                    self.line.push(c);
+                }
+            }
+        }
+    }
+
+    // Read at most one line from the read buffer
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut n = 0;
+        while n < buf.len() {
+            if let Some(c) = self.read_buf.pop() {
+                buf[n] = c;
+                n += 1;
+                if c == b'\n' {
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+        n
+    }
+}
+
+lazy_static! {
+    static ref TTY: Locked<Tty> = Locked::new(Tty::new());
+}
+
+// Feed the character received from console
+pub fn input(c: u8) {
+    TTY.lock().input(c);
+}
+
+/* Read the input of console in canonical mode. It will block until a
+ * line is available. Return None if it is interrupted by signal. */
+pub fn read(buf: &mut [u8]) -> Option<usize> {
+    loop {
+        /* Disable interrupt before checking the read buffer, so the
+         * input won't arrive before we go to sleep. */
+        cpu::intr_off();
+
+        let mut tty = TTY.lock();
+        if !tty.read_buf.is_empty() {
+            return Some(tty.read(buf));
+        }
+
+        if tty.eof > 0 {
+            tty.eof -= 1;
+            return Some(0);
+        }
+
+        let cur = sched::current();
+        let pid = unsafe { (*cur).id.0 };
+        if unsafe { (*cur).signal().has_pending() } {
+            tty.readers.retain(|&p| p != pid);
+            return None;
+        }
+
+        if !tty.readers.contains(&pid) {
+            tty.readers.push(pid);
+        }
+        drop(tty);
+
+        sched::sleep();
+    }
+}
+
+pub fn get_fg_pgrp() -> Option<u32> {
+    TTY.lock().fg_pgrp
+}
+
+pub fn set_fg_pgrp(pgrp: u32) {
+    TTY.lock().fg_pgrp = Some(pgrp);
+}
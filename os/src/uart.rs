@@ -1,5 +1,6 @@
 // Reference: http://byterunner.com/16550.html
 use crate::lock::Locked;
+use crate::tty;
 use crate::utils::ringbuf::RingBuf;
 use core::convert::TryInto;
 use lazy_static::lazy_static;
@@ -28,13 +29,29 @@ mmap_reg!(uart_lcr, 0x1000_0000 + 3, u8);
 // Line status register
 mmap_reg!(uart_lsr, 0x1000_0000 + 5, u8);
 
+// IER: receiver ready interrupt
+const IER_RX_ENABLE: u8 = 1 << 0;
+// IER: transmit holding register empty interrupt
+const IER_TX_ENABLE: u8 = 1 << 1;
+// LSR: data is ready in receive holding register
+const LSR_RX_READY: u8 = 1 << 0;
+// LSR: transmit holding register is empty
+const LSR_TX_IDLE: u8 = 1 << 5;
+
+lazy_static! {
+    // The characters which are waiting for transmission
+    static ref TX_BUFFER: Locked<RingBuf<u8, 1024>> = Locked::new(RingBuf::new());
+}
+
 pub fn init() {
     // set word length to 8 bits
     uart_lcr::write(0x3);
     // enable the transmit and receive FIFO
     uart_fcr::write(0x1);
-    // enable the receiver ready interrupt
-    uart_ier::write(0x1);
+    /* enable the receiver ready interrupt, the transmit holding
+     * register empty interrupt is enabled only when we have
+     * something to send */
+    uart_ier::write(IER_RX_ENABLE);
 
     /* Note: the os dosen't try on a real hardware now, so maybe we should fix this
      *
@@ -61,10 +78,45 @@ pub fn init() {
     uart_lcr::write(lcr_value);
 }
 
+/* Move the buffered characters to transmit holding register as many as
+ * possible. If the UART is busy, the rest of them will be sent after the
+ * transmit holding register empty interrupt. */
+fn uart_start(tx: &mut RingBuf<u8, 1024>) {
+    loop {
+        if tx.is_empty() {
+            uart_ier::write(IER_RX_ENABLE);
+            return;
+        }
+
+        if uart_lsr::read() & LSR_TX_IDLE == 0 {
+            uart_ier::write(IER_RX_ENABLE | IER_TX_ENABLE);
+            return;
+        }
+
+        uart_thr::write(tx.pop().unwrap());
+    }
+}
+
 pub fn uart_put(c: u8) {
+    /* Note that the interrupt should be disabled by the caller, because
+     * the buffer will also be accessed in the interrupt handler. */
+    let mut tx = TX_BUFFER.lock();
+
+    /* If the buffer is full, we have no choice but waiting for
+     * the UART to send one character out. */
+    if tx.is_full() {
+        uart_put_sync(tx.pop().unwrap());
+    }
+
+    tx.push(c);
+    uart_start(&mut tx);
+}
+
+// Send the character by busy waiting, which is used in panic only
+pub fn uart_put_sync(c: u8) {
     // wait for transmit holding register is not full
     loop {
-        match uart_lsr::read() & 0x20 == 0 {
+        match uart_lsr::read() & LSR_TX_IDLE == 0 {
             true => {
                 continue;
             }
@@ -76,10 +128,21 @@ pub fn uart_put(c: u8) {
     uart_thr::write(c);
 }
 
+/* Send all the buffered characters by busy waiting, which is used in
+ * panic only. We don't wait for the lock because the panic may happen
+ * when holding it. */
+pub fn uart_flush_sync() {
+    if let Some(mut tx) = TX_BUFFER.try_lock() {
+        while let Some(c) = tx.pop() {
+            uart_put_sync(c);
+        }
+    }
+}
+
 pub fn uart_get() -> u8 {
     // wait for receive holding register is not empty
     loop {
-        match uart_lsr::read() & 1 == 0 {
+        match uart_lsr::read() & LSR_RX_READY == 0 {
             true => {
                 continue;
             }
@@ -91,20 +154,12 @@ pub fn uart_get() -> u8 {
     uart_rhr::read()
 }
 
-lazy_static! {
-    static ref READ_BUFFER: Locked<RingBuf<u8, 512>> = Locked::new(RingBuf::new());
-}
-
 pub fn irq_handler() {
-    let c = uart_get();
-
-    /* TODO: Collect character in the ring buffer without
-     * consuming it. We'll take care of this once console is supported
-     * in the future. */
-    READ_BUFFER.lock().push(c);
-
-    // FIXME: Echo the character for checking now
-    if (c as char).is_ascii_alphanumeric() {
-        print!("{}", c as char);
+    // Pass all the received characters to the line discipline
+    while uart_lsr::read() & LSR_RX_READY != 0 {
+        tty::input(uart_get());
     }
+
+    // Continue the transmission if the UART is ready
+    uart_start(&mut TX_BUFFER.lock());
 }
@@ -1,13 +1,17 @@
 use core::mem;
 
-pub fn to_struct<T: plain::Plain>(args: &[u8]) -> &T {
-    let size = mem::size_of::<T>();
-    let slice = &args[0..size];
-    plain::from_bytes::<T>(slice).expect("Fail to cast bytes to Args")
-}
-
 pub fn to_struct_mut<T: plain::Plain>(args: &mut [u8]) -> &mut T {
     let size = mem::size_of::<T>();
     let slice = &mut args[0..size];
     plain::from_mut_bytes::<T>(slice).expect("Fail to cast bytes to Args")
 }
+
+pub fn as_bytes<T: plain::Plain>(t: &T) -> &[u8] {
+    // This is safe because any Plain type can be represented as bytes
+    unsafe { plain::as_bytes(t) }
+}
+
+pub fn as_bytes_mut<T: plain::Plain>(t: &mut T) -> &mut [u8] {
+    // This is safe because any bytes are valid for a Plain type
+    unsafe { plain::as_mut_bytes(t) }
+}
@@ -1,3 +1,2 @@
 pub mod cast;
-pub mod cstr;
 pub mod ringbuf;
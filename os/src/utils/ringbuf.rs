@@ -28,6 +28,14 @@ impl<T: Default + Copy, const N: usize> RingBuf<T, N> {
         self.tail - self.head > (N - 1)
     }
 
+    pub fn len(&self) -> usize {
+        self.tail - self.head
+    }
+
+    pub fn clear(&mut self) {
+        self.head = self.tail;
+    }
+
     pub fn push(&mut self, v: T) {
         if self.is_full() {
             // drop the value if the buffer is full
@@ -37,6 +45,23 @@ impl<T: Default + Copy, const N: usize> RingBuf<T, N> {
         self.tail += 1;
     }
 
+    // Push the value and drop the oldest one if the buffer is full
+    pub fn push_overwrite(&mut self, v: T) {
+        if self.is_full() {
+            self.head += 1;
+        }
+        self.buf[self.tail & (N - 1)] = v;
+        self.tail += 1;
+    }
+
+    // Get the i-th value from the oldest one without removing it
+    pub fn get(&self, i: usize) -> Option<T> {
+        if i >= self.len() {
+            return None;
+        }
+        Some(self.buf[(self.head + i) & (N - 1)])
+    }
+
     pub fn pop(&mut self) -> Option<T> {
         if self.is_empty() {
             return None;
@@ -46,3 +71,49 @@ impl<T: Default + Copy, const N: usize> RingBuf<T, N> {
         Some(v)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn push_pop_in_order() {
+        let mut ring = RingBuf::<u8, 4>::new();
+        assert!(ring.is_empty());
+
+        for i in 0..3 {
+            ring.push(i);
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.pop(), Some(0));
+        assert_eq!(ring.pop(), Some(1));
+        assert_eq!(ring.pop(), Some(2));
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test_case]
+    fn push_drop_when_full() {
+        let mut ring = RingBuf::<u8, 4>::new();
+        for i in 0..6 {
+            ring.push(i);
+        }
+        assert!(ring.is_full());
+        assert_eq!(ring.pop(), Some(0));
+        assert_eq!(ring.get(3), None);
+        assert_eq!(ring.get(2), Some(3));
+    }
+
+    #[test_case]
+    fn push_overwrite_oldest() {
+        let mut ring = RingBuf::<u8, 4>::new();
+        for i in 0..6 {
+            ring.push_overwrite(i);
+        }
+        assert_eq!(ring.len(), 4);
+        assert_eq!(ring.get(0), Some(2));
+        assert_eq!(ring.get(3), Some(5));
+
+        ring.clear();
+        assert!(ring.is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+use std::env;
+use std::fs::{self as host_fs, OpenOptions};
+use std::process::exit;
+
+use fs::*;
+
+type Fs = FileSystem<FileDevice>;
+
+fn usage(prog: &str) -> ! {
+    println!("Usage: {} <image> <command> [args]...", prog);
+    println!("Inspect the image or copy files in and out of it.");
+    println!();
+    println!("Commands:");
+    println!("  sb                     print the superblock");
+    println!("  ls <path>              list the directory");
+    println!("  stat <path>            print the inode, not following link");
+    println!("  bmap <path>            print the blocks of inode");
+    println!("  bitmap                 print the allocated blocks");
+    println!("  get <path> <host>      copy the file out to host");
+    println!("  put <host> <path>      copy the host file into image");
+    println!();
+    println!("The path in image should be absolute, or \"#N\" for inode N.");
+    exit(1);
+}
+
+fn fail(msg: String) -> ! {
+    println!("{}", msg);
+    exit(1);
+}
+
+fn type_name(typ: u16) -> &'static str {
+    match typ {
+        T_DIR => "dir",
+        T_FILE => "file",
+        T_DEVICE => "dev",
+        T_SYMLINK => "link",
+        0 => "free",
+        _ => "unknown",
+    }
+}
+
+// Format the timestamp as seconds since epoch
+fn format_time(ns: u64) -> String {
+    format!("{}.{:09}", ns / 1_000_000_000, ns % 1_000_000_000)
+}
+
+/* Resolve the path in image, which can also be "#N" for inode N. The
+ * last symbolic link is followed if `follow` is true. */
+fn lookup(fs: &Fs, path: &str, follow: bool) -> u32 {
+    let inum = match path.strip_prefix('#') {
+        Some(n) => n.parse().ok().filter(|&n| n != 0 && n <= fs.sb().ninodes),
+        None => fs.lookup(ROOTINO, path, follow).ok(),
+    };
+    inum.unwrap_or_else(|| fail(format!("{}: no such file or directory", path)))
+}
+
+fn print_sb(fs: &Fs) {
+    let sb = fs.sb();
+    println!("magic:      {:#x}", sb.magic);
+    println!("version:    {}", sb.version);
+    println!("size:       {} blocks", sb.fs_blksz);
+    println!("data:       {} blocks", sb.nblocks);
+    println!("inodes:     {}", sb.ninodes);
+    println!("log:        {} blocks from {}", sb.nlog, sb.logstart);
+    println!(
+        "inode:      {} blocks from {}",
+        sb.inode_blksz(),
+        sb.inodestart
+    );
+    println!(
+        "bitmap:     {} blocks from {}",
+        sb.bitmap_blksz(),
+        sb.bmapstart
+    );
+    println!("data start: {}", sb.nmeta());
+}
+
+fn list_dir(fs: &Fs, path: &str) {
+    let dir = fs.read_inode(lookup(fs, path, true));
+    if dir.typ != T_DIR {
+        fail(format!("{}: not a directory", path));
+    }
+
+    let mut off = 0;
+    while let Some(dirent) = fs.read_dirent(&dir, off) {
+        off += dirent.rec_len as usize;
+        if dirent.inum == 0 {
+            continue;
+        }
+
+        let inode = fs.read_inode(dirent.inum);
+        let target = match fs.read_symlink(&inode) {
+            Some(target) => format!(" -> {}", target),
+            None => String::new(),
+        };
+        println!(
+            "{:>5} {:<4} {:04o} {:>3} {:>8} {}{}",
+            dirent.inum,
+            type_name(inode.typ),
+            inode.mode,
+            inode.nlink,
+            inode.size,
+            String::from_utf8_lossy(dirent.name()),
+            target
+        );
+    }
+}
+
+fn stat(fs: &Fs, path: &str) {
+    let inum = lookup(fs, path, false);
+    let inode = fs.read_inode(inum);
+    println!("inode:  {}", inum);
+    println!("type:   {} ({})", type_name(inode.typ), inode.typ);
+    if let Some(target) = fs.read_symlink(&inode) {
+        println!("target: {}", target);
+    }
+    println!("mode:   {:04o}", inode.mode);
+    println!("owner:  {}:{}", inode.uid, inode.gid);
+    println!("device: {},{}", inode.major, inode.minor);
+    println!("nlink:  {}", inode.nlink);
+    println!("size:   {}", inode.size);
+    println!(
+        "blocks: {}",
+        (0..FILE_MAX_LINK)
+            .filter(|&bn| fs.bmap(&inode, bn).is_some())
+            .count()
+    );
+    println!("atime:  {}", format_time(inode.atime));
+    println!("mtime:  {}", format_time(inode.mtime));
+    println!("ctime:  {}", format_time(inode.ctime));
+}
+
+fn print_bmap(fs: &Fs, path: &str) {
+    let inode = fs.read_inode(lookup(fs, path, false));
+    if inode.indirect != 0 {
+        println!("indirect: {}", inode.indirect);
+    }
+    for bn in 0..FILE_MAX_LINK {
+        if let Some(block_no) = fs.bmap(&inode, bn) {
+            println!("{:>5}: {}", bn, block_no);
+        }
+    }
+}
+
+// Print the allocated blocks in ranges
+fn print_bitmap(fs: &Fs) {
+    let sb = *fs.sb();
+    let mut buf = vec![0; BLKSZ];
+    let mut start = None;
+    let mut used = 0;
+
+    for block_no in 0..=sb.fs_blksz {
+        let marked = block_no < sb.fs_blksz && {
+            let bit = block_no as usize % BIT_PER_BLK;
+            if bit == 0 {
+                fs.device().read_block(block_bmap(&sb, block_no), &mut buf);
+            }
+            buf[bit / 8] & (1 << (bit % 8)) != 0
+        };
+
+        match (marked, start) {
+            (true, None) => start = Some(block_no),
+            (false, Some(first)) => {
+                println!("{}-{}", first, block_no - 1);
+                used += block_no - first;
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    println!("{} of {} blocks in use", used, sb.fs_blksz);
+}
+
+fn get(fs: &Fs, path: &str, host: &str) {
+    let inode = fs.read_inode(lookup(fs, path, true));
+    if inode.typ != T_FILE {
+        fail(format!("{}: not a regular file", path));
+    }
+
+    let mut data = vec![0; inode.size as usize];
+    let n = fs.readi(&inode, 0, &mut data);
+    host_fs::write(host, &data[..n]).unwrap_or_else(|e| fail(format!("write {}: {}", host, e)));
+}
+
+/* Copy the host file to the path. The file is overwritten if it exists,
+ * otherwise it is created under the parent directory. */
+fn put(fs: &Fs, host: &str, path: &str) {
+    let data = host_fs::read(host).unwrap_or_else(|e| fail(format!("read {}: {}", host, e)));
+
+    let inum = match fs.namei(ROOTINO, path) {
+        Some(inum) => inum,
+        None => {
+            let (dir, name) = fs
+                .nameiparent(ROOTINO, path)
+                .unwrap_or_else(|| fail(format!("{}: invalid path", path)));
+            fs.create(dir, name, T_FILE, 0, 0)
+                .unwrap_or_else(|| fail(format!("{}: invalid name or no space", path)))
+        }
+    };
+
+    let mut inode = fs.read_inode(inum);
+    if inode.typ != T_FILE {
+        fail(format!("{}: not a regular file", path));
+    }
+
+    fs.itrunc(&mut inode);
+    let n = fs.writei(&mut inode, 0, &data);
+    fs.write_inode(inum, &inode);
+
+    if n != Some(data.len()) {
+        fail(format!("{}: too large or the image is full", host));
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        usage(&args[0]);
+    }
+
+    let img_name = &args[1];
+    let cmd: Vec<&str> = args[2..].iter().map(|s| s.as_str()).collect();
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(cmd[0] == "put")
+        .open(img_name)
+        .unwrap_or_else(|e| fail(format!("open {}: {}", img_name, e)));
+    let len = file.metadata().expect("metadata()").len();
+    if len < 2 * BLKSZ as u64 {
+        fail(format!("{}: too small to be an image", img_name));
+    }
+
+    let fs = FileSystem::open(FileDevice::new(file))
+        .unwrap_or_else(|e| fail(format!("{}: {}", img_name, e)));
+    if len < fs.sb().fs_blksz as u64 * BLKSZ as u64 {
+        fail(format!("{}: the image is truncated", img_name));
+    }
+
+    match cmd[..] {
+        ["sb"] => print_sb(&fs),
+        ["ls"] => list_dir(&fs, "/"),
+        ["ls", path] => list_dir(&fs, path),
+        ["stat", path] => stat(&fs, path),
+        ["bmap", path] => print_bmap(&fs, path),
+        ["bitmap"] => print_bitmap(&fs),
+        ["get", path, host] => get(&fs, path, host),
+        ["put", host, path] => put(&fs, host, path),
+        _ => usage(&args[0]),
+    }
+}